@@ -1,14 +1,16 @@
 use btc_collider_rs::collider::Collider;
+use btc_collider_rs::network::Network;
+use btc_collider_rs::script_template::ScriptTemplate;
 use btc_collider_rs::search_space::SearchSpace;
+use btc_collider_rs::targets::Targets;
 use btc_collider_rs::{hash_util, key_util};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use num_bigint::BigUint;
 use secp256k1::Secp256k1;
-use std::collections::HashSet;
 
 fn criterion_benchmark(c: &mut Criterion) {
     let secp = Secp256k1::new();
-    let addresses = HashSet::new();
+    let targets = Targets::new();
 
     let start: u64 = 1;
     let search_space = SearchSpace {
@@ -17,8 +19,10 @@ fn criterion_benchmark(c: &mut Criterion) {
     };
 
     let collider = Collider {
-        addresses: &addresses,
+        targets: &targets,
         secp: &secp,
+        network: Network::Mainnet,
+        script_templates: ScriptTemplate::DEFAULT,
     };
 
     c.bench_function("collider", |b| {
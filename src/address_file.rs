@@ -1,93 +1,1557 @@
 use crate::btc_address;
-use crate::btc_address::BTCAddressType;
-use flate2::read::GzDecoder;
-use log::{debug, info};
-use primitive_types::H160;
-use std::collections::HashSet;
+use crate::btc_address::{AddressTypes, BTCAddressType};
+use crate::descriptor;
+use crate::error::Error;
+use crate::network::Network;
+use crate::targets::{Labels, Target, TargetInfo, Targets};
+use crate::utxo_snapshot::{SnapshotReader, SNAPSHOT_MAGIC};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use log::{debug, info, warn};
+use primitive_types::H256;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io;
-use std::io::{BufRead, Read};
-use std::time::SystemTime;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use std::{fs, io, thread};
 
+/// Number of lines the reader hands to a parser thread at once
+const BATCH_SIZE: usize = 16_384;
+/// Time between two progress messages while loading
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of invalid lines a `LoadReport` keeps with their reason
+const MAX_REPORTED_INVALID_LINES: usize = 100;
+
+/// Number of lines of address files per address type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AddressCount {
+    pub p2pkh: u64,
+    pub p2sh: u64,
+    pub p2wpkh: u64,
+    pub p2wsh: u64,
+    pub p2tr: u64,
+    pub p2pk: u64,
+    pub misc: u64,
+    /// Lines that are not a valid target
+    pub err: u64,
+    pub below_min_balance: u64,
+}
+
+impl AddressCount {
+    pub(crate) fn count(&mut self, address_type: BTCAddressType) {
+        self.count_many(address_type, 1);
+    }
+
+    fn count_many(&mut self, address_type: BTCAddressType, n: u64) {
+        match address_type {
+            BTCAddressType::P2PKH => self.p2pkh += n,
+            BTCAddressType::P2SH => self.p2sh += n,
+            BTCAddressType::P2WPKH => self.p2wpkh += n,
+            BTCAddressType::P2WSH => self.p2wsh += n,
+            BTCAddressType::P2TR => self.p2tr += n,
+            BTCAddressType::P2PK => self.p2pk += n,
+            BTCAddressType::MISC => self.misc += n,
+            BTCAddressType::ERR => self.err += n,
+        }
+    }
+
+    fn add(&mut self, other: &AddressCount) {
+        self.p2pkh += other.p2pkh;
+        self.p2sh += other.p2sh;
+        self.p2wpkh += other.p2wpkh;
+        self.p2wsh += other.p2wsh;
+        self.p2tr += other.p2tr;
+        self.p2pk += other.p2pk;
+        self.misc += other.misc;
+        self.err += other.err;
+        self.below_min_balance += other.below_min_balance;
+    }
+
+    pub fn lines(&self) -> u64 {
+        self.p2pkh
+            + self.p2sh
+            + self.p2wpkh
+            + self.p2wsh
+            + self.p2tr
+            + self.p2pk
+            + self.misc
+            + self.err
+            + self.below_min_balance
+    }
+}
+
+/// A line of an address file that is not a valid target.
+#[derive(Debug)]
+pub struct InvalidLine {
+    pub file: String,
+    pub line_number: usize,
+    pub line: String,
+    pub error: Error,
+}
+
+/// What loading address files found besides the targets.
 #[derive(Debug, Default)]
-struct AddressCount {
-    p2pk: u64,
-    p2sh: u64,
-    p2wpkh: u64,
-    p2wsh: u64,
-    misc: u64,
-    err: u64,
+pub struct LoadReport {
+    pub address_count: AddressCount,
+    /// Lines listing a target with a type an earlier line already listed it as
+    pub duplicates: u64,
+    /// The first invalid lines, `address_count.err` has the number of all of them
+    pub invalid_lines: Vec<InvalidLine>,
 }
 
-pub fn read_addresses_file(file_name: &str) -> HashSet<H160> {
-    let file = File::open(file_name).expect("Could not open address file");
+/// What to do with lines that are not a valid target.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Strictness {
+    /// Stop loading with the error of the first invalid line
+    Fail,
+    /// Skip invalid lines with a warning
+    #[default]
+    Skip,
+    /// Skip invalid lines and write them with the reason to the given rejects file
+    Reject(String),
+}
 
-    let reader: Box<dyn Read> = Box::new(GzDecoder::new(file));
-    let mut address_count = AddressCount {
-        ..Default::default()
+impl FromStr for Strictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Strictness::Fail),
+            "skip" => Ok(Strictness::Skip),
+            _ => match s.strip_prefix("reject:") {
+                Some(file) if !file.is_empty() => Ok(Strictness::Reject(file.to_string())),
+                _ => Err(format!(
+                    "unknown strictness '{}', expected fail, skip or reject:<file>",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Compression of an address file, detected from its magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Plain,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    pub fn detect(header: &[u8]) -> Compression {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if header.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::Plain
+        }
+    }
+}
+
+/// Opens an address file, or stdin for `-`, and decompresses it according to its magic bytes.
+pub fn open_address_file(file_name: &str) -> Result<Box<dyn Read + Send>, Error> {
+    open_counted(file_name, Arc::new(AtomicU64::new(0)))
+}
+
+/// Creates an address file and writes it with `write`, gzip compressed if the name ends with `.gz`.
+pub fn create_address_file<T>(
+    file_name: &str,
+    write: impl FnOnce(&mut dyn Write) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let io_error = |e| Error::io(file_name, e);
+    let file = BufWriter::new(File::create(file_name).map_err(io_error)?);
+    if file_name.ends_with(".gz") {
+        let mut writer = GzEncoder::new(file, flate2::Compression::default());
+        let result = write(&mut writer)?;
+        writer
+            .finish()
+            .and_then(|mut file| file.flush())
+            .map_err(io_error)?;
+        Ok(result)
+    } else {
+        let mut writer = file;
+        let result = write(&mut writer)?;
+        writer.flush().map_err(io_error)?;
+        Ok(result)
+    }
+}
+
+/// Counts the bytes read from the file before decompression, for progress and size estimates.
+struct CountingReader<R> {
+    inner: R,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+fn open_counted(
+    file_name: &str,
+    bytes_read: Arc<AtomicU64>,
+) -> Result<Box<dyn Read + Send>, Error> {
+    let input: Box<dyn Read + Send> = if file_name == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(file_name).map_err(|e| Error::io(file_name, e))?)
+    };
+    let input = CountingReader {
+        inner: input,
+        bytes_read,
     };
+    let mut input = BufReader::new(input);
+    let compression = Compression::detect(input.fill_buf().map_err(|e| Error::io(file_name, e))?);
+    debug!("Address file {} is {:?}", file_name, compression);
 
-    let mut addresses_set = HashSet::new();
-    //let mut addresses_set = HashSet::with_capacity(117440512);
-    let start_time = SystemTime::now();
-    for line in io::BufReader::new(reader).lines() {
-        let line = line.unwrap();
-        let line = line.trim();
-        let address_type = btc_address::get_address_type(line);
-        match address_type {
-            BTCAddressType::P2PK => {
-                address_count.p2pk += 1;
-                addresses_set.insert(btc_address::p2pk_address_to_160_bit_hash(line));
-            }
-            BTCAddressType::P2SH => {
-                address_count.p2sh += 1;
-            }
-            BTCAddressType::P2WPKH => {
-                address_count.p2wpkh += 1;
-                addresses_set.insert(btc_address::p2wpkh_address_to_160_bit_hash(line));
-            }
-            BTCAddressType::P2WSH => {
-                address_count.p2wsh += 1;
-            }
-            BTCAddressType::MISC => {
-                address_count.misc += 1;
-            }
-            BTCAddressType::ERR => {
-                address_count.err += 1;
+    let reader: Box<dyn Read + Send> = match compression {
+        Compression::Plain => Box::new(input),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+        Compression::Zstd => {
+            Box::new(zstd::Decoder::with_buffer(input).map_err(|e| Error::io(file_name, e))?)
+        }
+        Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(input)),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(input)),
+    };
+    Ok(reader)
+}
+
+/// Layout of the lines of an address file. A plain list of addresses is a table with one column.
+#[derive(Clone, Debug)]
+pub struct Columns {
+    pub delimiter: char,
+    /// Index of the address column, starting at 0
+    pub address: usize,
+    /// Index of the balance column in satoshis, starting at 0
+    pub balance: Option<usize>,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            delimiter: '\t',
+            address: 0,
+            balance: None,
+        }
+    }
+}
+
+impl Columns {
+    /// The address and balance of a line.
+    fn split<'a>(&self, line: &'a str) -> Result<(&'a str, Option<u64>), Error> {
+        let fields: Vec<&str> = line
+            .split(self.delimiter)
+            .map(|field| field.trim().trim_matches('"'))
+            .collect();
+        let field = |column: usize| fields.get(column).ok_or(Error::MissingColumn(column));
+
+        let address = field(self.address)?;
+        let balance = match self.balance {
+            Some(column) => {
+                let balance = field(column)?;
+                Some(
+                    balance
+                        .parse::<u64>()
+                        .map_err(|_| Error::InvalidBalance(balance.to_string()))?,
+                )
             }
+            None => None,
         };
+        Ok((address, balance))
     }
+}
+
+/// How address files are read.
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    pub network: Network,
+    pub strictness: Strictness,
+    pub columns: Columns,
+    /// Skip the first line, the header of a table
+    pub skip_header: bool,
+    /// Only load targets with at least this balance in satoshis, targets without balance are
+    /// dropped
+    pub min_balance: Option<u64>,
+    /// Number of threads parsing lines, 0 for one per CPU
+    pub threads: usize,
+    /// Write valid lines of types the collider does not match to the rejects file as well
+    pub reject_unmatched: bool,
+}
+
+/// An address file, or a directory of address files, and the label of its targets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressSource {
+    /// Label of the targets, the file name without extensions if not given
+    pub label: Option<String>,
+    pub path: String,
+}
+
+impl AddressSource {
+    pub fn new(path: &str) -> Self {
+        AddressSource {
+            label: None,
+            path: path.to_string(),
+        }
+    }
+
+    /// The files of the source with their labels, the files of a directory in name order.
+    pub fn files(&self) -> Result<Vec<(String, String)>, Error> {
+        let path = Path::new(&self.path);
+        let files = if path.is_dir() {
+            let mut files = fs::read_dir(path)
+                .map_err(|e| Error::io(&self.path, e))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<PathBuf>, _>>()
+                .map_err(|e| Error::io(&self.path, e))?;
+            files.retain(|file| {
+                file.is_file()
+                    && !file
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            });
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        Ok(files
+            .into_iter()
+            .map(|file| {
+                let label = match &self.label {
+                    Some(label) => label.clone(),
+                    None => Self::file_label(&file),
+                };
+                (label, file.to_string_lossy().to_string())
+            })
+            .collect())
+    }
+
+    /// The label of the source itself: the given label or the name of the file or directory.
+    pub fn label(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => Self::file_label(Path::new(&self.path)),
+        }
+    }
+
+    fn file_label(file: &Path) -> String {
+        if file == Path::new("-") {
+            return "stdin".to_string();
+        }
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        name.split('.').next().unwrap_or_default().to_string()
+    }
+}
+
+impl FromStr for AddressSource {
+    type Err = String;
+
+    /// `<label>=<path>` or `<path>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("", _)) => Err(format!("empty label in '{}'", s)),
+            Some((_, "")) => Err(format!("empty path in '{}'", s)),
+            Some((label, path)) => Ok(AddressSource {
+                label: Some(label.to_string()),
+                path: path.to_string(),
+            }),
+            None => Ok(AddressSource::new(s)),
+        }
+    }
+}
+
+/// Rejected lines of all loaded address files.
+struct Rejects {
+    file: String,
+    writer: BufWriter<File>,
+}
+
+impl Rejects {
+    fn write(
+        &mut self,
+        file_name: &str,
+        line_number: usize,
+        line: &str,
+        error: &Error,
+    ) -> Result<(), Error> {
+        debug!("Rejecting line {}: {}", line_number, error);
+        writeln!(
+            self.writer,
+            "{}:{}\t{}\t{}",
+            file_name, line_number, line, error
+        )
+        .map_err(|e| Error::io(&self.file, e))
+    }
+}
+
+impl LoadOptions {
+    pub(crate) fn has_min_balance(&self, balance: Option<u64>) -> bool {
+        match (self.min_balance, balance) {
+            (Some(min_balance), Some(balance)) => balance >= min_balance,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+enum ParsedLine {
+    Target(BTCAddressType, Target, TargetInfo),
+    /// Valid line of a type the collider does not match
+    Unmatched {
+        address_type: BTCAddressType,
+        line_number: usize,
+        line: String,
+    },
+    BelowMinBalance,
+    Invalid {
+        line_number: usize,
+        line: String,
+        error: Error,
+    },
+}
+
+struct LineBatch {
+    index: usize,
+    first_line_number: usize,
+    lines: Vec<String>,
+    /// Bytes of the file read when the batch was complete
+    bytes_read: u64,
+}
+
+struct ParsedBatch {
+    index: usize,
+    lines: Vec<ParsedLine>,
+    bytes_read: u64,
+}
+
+/// Reads a single address file, its targets are labelled with its file name.
+pub fn read_addresses_file(file_name: &str, options: &LoadOptions) -> Result<Targets, Error> {
+    read_address_sources(&[AddressSource::new(file_name)], options)
+}
+
+/// Reads all files of the sources into one target set that keeps the labels of each target.
+pub fn read_address_sources(
+    sources: &[AddressSource],
+    options: &LoadOptions,
+) -> Result<Targets, Error> {
+    read_address_sources_with_report(sources, options).map(|(targets, _)| targets)
+}
+
+/// Reads all files of the sources like `read_address_sources` and reports the counted lines,
+/// duplicates and invalid lines of all files.
+pub fn read_address_sources_with_report(
+    sources: &[AddressSource],
+    options: &LoadOptions,
+) -> Result<(Targets, LoadReport), Error> {
+    let mut rejects = match &options.strictness {
+        Strictness::Reject(rejects_file) => Some(Rejects {
+            file: rejects_file.clone(),
+            writer: BufWriter::new(
+                File::create(rejects_file).map_err(|e| Error::io(rejects_file, e))?,
+            ),
+        }),
+        _ => None,
+    };
+
+    let mut targets = Targets::new();
+    let mut report = LoadReport::default();
+    for source in sources {
+        for (label, file_name) in source.files()? {
+            let labels = targets.add_label(&label)?;
+            read_into(
+                &mut targets,
+                &mut report,
+                &file_name,
+                &label,
+                labels,
+                rejects.as_mut(),
+                options,
+            )?;
+        }
+    }
+    if let Some(rejects) = &mut rejects {
+        rejects
+            .writer
+            .flush()
+            .map_err(|e| Error::io(&rejects.file, e))?;
+    }
+
+    for store in targets.store_stats() {
+        debug!(
+            "{} elements={}, capacity={}",
+            store.name, store.len, store.capacity
+        );
+    }
+
+    Ok((targets, report))
+}
+
+/// Reads an address file, or a UTXO snapshot recognized by its magic bytes, into the targets.
+fn read_into(
+    targets: &mut Targets,
+    report: &mut LoadReport,
+    file_name: &str,
+    label: &str,
+    labels: Labels,
+    rejects: Option<&mut Rejects>,
+    options: &LoadOptions,
+) -> Result<(), Error> {
+    let bytes_read = Arc::new(AtomicU64::new(0));
+    let mut reader = BufReader::new(open_counted(file_name, bytes_read.clone())?);
+    let is_snapshot = reader
+        .fill_buf()
+        .map_err(|e| Error::io(file_name, e))?
+        .starts_with(&SNAPSHOT_MAGIC);
+    let input = Input {
+        file_name,
+        file_size: match file_name {
+            "-" => None,
+            _ => fs::metadata(file_name).ok().map(|metadata| metadata.len()),
+        },
+        labels,
+    };
+    let targets_before = targets.len();
+
+    let start_time = SystemTime::now();
+    let address_count = if is_snapshot {
+        read_snapshot(reader, targets, report, &input, options)?
+    } else {
+        read_lines(
+            reader, bytes_read, targets, report, &input, rejects, options,
+        )?
+    };
     let end_time = SystemTime::now();
     let time_taken = end_time.duration_since(start_time).unwrap().as_secs_f32();
 
     info!(
-        "Read {} bitcoin-addresses from {} in {:.2}s",
-        addresses_set.len(),
+        "Read {} bitcoin-addresses from {} ({}) in {:.2}s",
+        targets.len() - targets_before,
         file_name,
+        label,
         time_taken
     );
     info!("{:#?}", address_count);
+    report.address_count.add(&address_count);
+
+    Ok(())
+}
+
+/// Reads the lines of an address file in three stages: one thread decompresses and splits it into
+/// batches of lines, parser threads decode and hash the batches, and the calling thread inserts the
+/// targets in the order of the file.
+fn read_lines(
+    reader: impl BufRead + Send,
+    bytes_read: Arc<AtomicU64>,
+    targets: &mut Targets,
+    report: &mut LoadReport,
+    input: &Input,
+    rejects: Option<&mut Rejects>,
+    options: &LoadOptions,
+) -> Result<AddressCount, Error> {
+    let threads = match options.threads {
+        0 => num_cpus::get(),
+        threads => threads,
+    };
+
+    thread::scope(|scope| {
+        let (line_tx, line_rx) = mpsc::sync_channel(threads * 2);
+        let (parsed_tx, parsed_rx) = mpsc::sync_channel(threads * 2);
+
+        let reader = scope.spawn(move || {
+            read_batches(reader, options.skip_header, &bytes_read, line_tx)
+                .map_err(|e| Error::io(input.file_name, e))
+        });
+        let line_rx = Arc::new(Mutex::new(line_rx));
+        for _ in 0..threads {
+            let line_rx = line_rx.clone();
+            let parsed_tx = parsed_tx.clone();
+            scope.spawn(move || parse_batches(&line_rx, parsed_tx, options));
+        }
+        // the stages stop once the receivers of their output are gone
+        drop(line_rx);
+        drop(parsed_tx);
+
+        let result = collect(parsed_rx, targets, report, input, rejects, options);
+        let read = reader.join().expect("Address file reader panicked");
+        result.and_then(|result| read.map(|_| result))
+    })
+}
+
+/// Inserts the targets of the coins of a UTXO snapshot with the sum of their amounts as balance. The
+/// minimum balance applies to that sum, the coins of a target below it count as below the minimum.
+fn read_snapshot(
+    reader: impl Read,
+    targets: &mut Targets,
+    report: &mut LoadReport,
+    input: &Input,
+    options: &LoadOptions,
+) -> Result<AddressCount, Error> {
+    let mut snapshot = SnapshotReader::new(reader, input.file_name, options.network)?;
+    let coins_count = snapshot.metadata.coins_count;
+    // block hashes are shown in reverse byte order
+    let mut block_hash = snapshot.metadata.base_blockhash.0;
+    block_hash.reverse();
+    info!(
+        "Loading UTXO snapshot {} of block {:x} with {} coins",
+        input.file_name,
+        H256(block_hash),
+        coins_count
+    );
+
+    let mut address_count = AddressCount::default();
+    let mut coins_read = 0u64;
+    let mut last_progress = SystemTime::now();
+    // the coins of an address are separate amounts, unlike repeated lines of an address file: sum
+    // of amounts and number of coins per target and type
+    let mut coins: HashMap<(Target, BTCAddressType), (u64, u64)> = HashMap::new();
+    for coin in &mut snapshot {
+        let coin = coin?;
+        coins_read += 1;
+        match coin.target {
+            Some(target) => {
+                let (amount, count) = coins.entry((target, coin.address_type)).or_default();
+                *amount = amount.saturating_add(coin.amount);
+                *count += 1;
+            }
+            None => address_count.count(coin.address_type),
+        }
+
+        if last_progress.elapsed().unwrap_or_default() >= PROGRESS_INTERVAL {
+            last_progress = SystemTime::now();
+            info!(
+                "Loading {}: {} coins, {:.0}%",
+                input.file_name,
+                coins_read,
+                coins_read as f64 * 100.0 / coins_count as f64
+            );
+        }
+    }
+
+    // a public key hash can hold P2PKH and P2WPKH coins, the minimum applies to both together
+    let mut balances: HashMap<Target, u64> = HashMap::new();
+    if options.min_balance.is_some() {
+        for ((target, _), (amount, _)) in &coins {
+            let balance = balances.entry(target.clone()).or_default();
+            *balance = balance.saturating_add(*amount);
+        }
+    }
+    for ((target, address_type), (amount, count)) in coins {
+        if options
+            .min_balance
+            .is_some_and(|min| balances[&target] < min)
+        {
+            address_count.below_min_balance += count;
+            continue;
+        }
+        address_count.count_many(address_type, count);
+        report.duplicates += count - 1;
+        // the balances of the types of a target add up on insert
+        let info = TargetInfo {
+            balance: Some(amount),
+            address_types: AddressTypes::of(address_type),
+            labels: input.labels,
+            type_balances: None,
+        };
+        if targets.insert(target, info) {
+            report.duplicates += 1;
+        }
+    }
+
+    Ok(address_count)
+}
+
+/// The address file the collected batches come from.
+struct Input<'a> {
+    file_name: &'a str,
+    file_size: Option<u64>,
+    labels: Labels,
+}
+
+fn read_batches(
+    reader: impl BufRead,
+    skip_header: bool,
+    bytes_read: &AtomicU64,
+    line_tx: SyncSender<LineBatch>,
+) -> io::Result<()> {
+    let mut batch = LineBatch {
+        index: 0,
+        first_line_number: skip_header as usize + 1,
+        lines: Vec::with_capacity(BATCH_SIZE),
+        bytes_read: 0,
+    };
+    for line in reader.lines().skip(skip_header as usize) {
+        batch.lines.push(line?);
+        if batch.lines.len() == BATCH_SIZE {
+            let next = LineBatch {
+                index: batch.index + 1,
+                first_line_number: batch.first_line_number + BATCH_SIZE,
+                lines: Vec::with_capacity(BATCH_SIZE),
+                bytes_read: 0,
+            };
+            batch.bytes_read = bytes_read.load(Ordering::Relaxed);
+            if line_tx.send(std::mem::replace(&mut batch, next)).is_err() {
+                // loading failed, nobody is waiting for more lines
+                return Ok(());
+            }
+        }
+    }
+    if !batch.lines.is_empty() {
+        batch.bytes_read = bytes_read.load(Ordering::Relaxed);
+        let _ = line_tx.send(batch);
+    }
+    Ok(())
+}
+
+fn parse_batches(
+    line_rx: &Mutex<Receiver<LineBatch>>,
+    parsed_tx: SyncSender<ParsedBatch>,
+    options: &LoadOptions,
+) {
+    loop {
+        let batch = match line_rx.lock().unwrap().recv() {
+            Ok(batch) => batch,
+            Err(_) => return,
+        };
+        let lines = batch
+            .lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| parse_line(line, batch.first_line_number + i, options))
+            .collect();
+        let parsed = ParsedBatch {
+            index: batch.index,
+            lines,
+            bytes_read: batch.bytes_read,
+        };
+        if parsed_tx.send(parsed).is_err() {
+            return;
+        }
+    }
+}
+
+fn parse_line(line: String, line_number: usize, options: &LoadOptions) -> ParsedLine {
+    let result = options
+        .columns
+        .split(line.trim())
+        .and_then(|(address, balance)| {
+            if !options.has_min_balance(balance) {
+                return Ok(ParsedLine::BelowMinBalance);
+            }
+            Ok(match parse_target(address, options.network)? {
+                (address_type, Some(target)) => {
+                    let info = TargetInfo {
+                        balance,
+                        address_types: AddressTypes::of(address_type),
+                        ..Default::default()
+                    };
+                    ParsedLine::Target(address_type, target, info)
+                }
+                (address_type, None) => ParsedLine::Unmatched {
+                    address_type,
+                    line_number,
+                    line: line.trim().to_string(),
+                },
+            })
+        });
+    result.unwrap_or_else(|error| ParsedLine::Invalid {
+        line_number,
+        line: line.trim().to_string(),
+        error,
+    })
+}
+
+pub(crate) fn parse_target(
+    line: &str,
+    network: Network,
+) -> Result<(BTCAddressType, Option<Target>), Error> {
+    if descriptor::is_descriptor(line) {
+        let (address_type, target) = descriptor::parse_descriptor(line, network)?;
+        return Ok((address_type, Some(target)));
+    }
+    let (address_type, hex) = match btc_address::split_type_tag(line) {
+        Some((address_type, hex)) => (address_type, Some(hex)),
+        None => (btc_address::get_address_type(line, network), None),
+    };
+    // a public key tagged with a hash address type stands for that address of the key, as written
+    // by `harvest-pubkeys`
+    if let (BTCAddressType::P2PKH | BTCAddressType::P2WPKH | BTCAddressType::P2SH, Some(hex)) =
+        (address_type, hex)
+    {
+        if hex.len() != 40 {
+            let (public_key, compressed) = btc_address::p2pk_output_to_public_key(hex)?;
+            return Ok((
+                address_type,
+                Some(Target::PublicKey(public_key, compressed)),
+            ));
+        }
+    }
+    let target = match address_type {
+        BTCAddressType::P2PKH => Target::PubkeyHash(match hex {
+            Some(hex) => btc_address::hex_to_160_bit_hash(hex)?,
+            None => btc_address::p2pkh_address_to_160_bit_hash(line, network)?,
+        }),
+        BTCAddressType::P2SH => Target::ScriptHash(match hex {
+            Some(hex) => btc_address::hex_to_160_bit_hash(hex)?,
+            None => btc_address::p2sh_address_to_160_bit_hash(line, network)?,
+        }),
+        BTCAddressType::P2WPKH => Target::PubkeyHash(match hex {
+            Some(hex) => btc_address::hex_to_160_bit_hash(hex)?,
+            None => btc_address::p2wpkh_address_to_160_bit_hash(line)?,
+        }),
+        BTCAddressType::P2WSH => Target::WitnessScriptHash(match hex {
+            Some(hex) => btc_address::hex_to_256_bit_hash(hex)?,
+            None => btc_address::p2wsh_address_to_script_hash(line)?,
+        }),
+        BTCAddressType::P2TR => Target::OutputKey(match hex {
+            Some(hex) => btc_address::hex_to_256_bit_hash(hex)?,
+            None => btc_address::p2tr_address_to_output_key(line)?,
+        }),
+        BTCAddressType::P2PK => {
+            let (public_key, compressed) =
+                btc_address::p2pk_output_to_public_key(hex.unwrap_or(line))?;
+            Target::PublicKey(public_key, compressed)
+        }
+        BTCAddressType::MISC => return Ok((address_type, None)),
+        BTCAddressType::ERR => return Err(btc_address::invalid_address_error(line, network)),
+    };
+    Ok((address_type, Some(target)))
+}
+
+/// Inserts the parsed batches in the order of the file, applying the strictness to invalid lines.
+fn collect(
+    parsed_rx: Receiver<ParsedBatch>,
+    targets: &mut Targets,
+    report: &mut LoadReport,
+    input: &Input,
+    mut rejects: Option<&mut Rejects>,
+    options: &LoadOptions,
+) -> Result<AddressCount, Error> {
+    let mut address_count = AddressCount {
+        ..Default::default()
+    };
+    let mut pending = BTreeMap::new();
+    let mut next_index = 0;
+    let mut last_progress = SystemTime::now();
+
+    for batch in parsed_rx {
+        pending.insert(batch.index, batch);
+        while let Some(batch) = pending.remove(&next_index) {
+            for line in batch.lines {
+                match line {
+                    ParsedLine::Target(address_type, target, mut info) => {
+                        address_count.count(address_type);
+                        info.labels = input.labels;
+                        if targets.insert(target, info) {
+                            report.duplicates += 1;
+                        }
+                    }
+                    ParsedLine::Unmatched {
+                        address_type,
+                        line_number,
+                        line,
+                    } => {
+                        address_count.count(address_type);
+                        if let (Some(rejects), true) = (&mut rejects, options.reject_unmatched) {
+                            let error = Error::UnmatchedTarget(address_type);
+                            rejects.write(input.file_name, line_number, &line, &error)?;
+                        }
+                    }
+                    ParsedLine::BelowMinBalance => address_count.below_min_balance += 1,
+                    ParsedLine::Invalid {
+                        line_number,
+                        line,
+                        error,
+                    } => {
+                        address_count.err += 1;
+                        match &mut rejects {
+                            _ if options.strictness == Strictness::Fail => {
+                                return Err(error.at_line(input.file_name, line_number))
+                            }
+                            Some(rejects) => {
+                                rejects.write(input.file_name, line_number, &line, &error)?
+                            }
+                            None => warn!(
+                                "Skipping {} line {}: {}",
+                                input.file_name, line_number, error
+                            ),
+                        }
+                        if report.invalid_lines.len() < MAX_REPORTED_INVALID_LINES {
+                            report.invalid_lines.push(InvalidLine {
+                                file: input.file_name.to_string(),
+                                line_number,
+                                line,
+                                error,
+                            });
+                        }
+                    }
+                }
+            }
+
+            if next_index == 0 {
+                reserve(targets, &address_count, batch.bytes_read, input.file_size);
+            }
+            if last_progress.elapsed().unwrap_or_default() >= PROGRESS_INTERVAL {
+                last_progress = SystemTime::now();
+                match input.file_size {
+                    Some(file_size) if file_size > 0 => info!(
+                        "Loading {}: {} lines, {:.0}%",
+                        input.file_name,
+                        address_count.lines(),
+                        batch.bytes_read as f64 * 100.0 / file_size as f64
+                    ),
+                    _ => info!(
+                        "Loading {}: {} lines",
+                        input.file_name,
+                        address_count.lines()
+                    ),
+                }
+            }
+            next_index += 1;
+        }
+    }
+
+    Ok(address_count)
+}
+
+/// Pre-sizes the targets from the first batch of a file: its lines per byte of the file give the
+/// estimated number of lines, its mix of address types how they are distributed.
+fn reserve(
+    targets: &mut Targets,
+    address_count: &AddressCount,
+    bytes_read: u64,
+    file_size: Option<u64>,
+) {
+    let (Some(file_size), true) = (file_size, bytes_read > 0) else {
+        return;
+    };
     debug!(
-        "Addresses set elements={}, capacity={}",
-        addresses_set.len(),
-        addresses_set.capacity()
+        "Estimated {} lines in address file",
+        address_count.lines() * file_size / bytes_read
     );
+    // additional capacity for the rest of the file
+    let scale = |loaded: u64| ((loaded * file_size / bytes_read).saturating_sub(loaded)) as usize;
 
-    addresses_set
+    targets
+        .pubkey_hashes
+        .reserve(scale(address_count.p2pkh + address_count.p2wpkh));
+    targets.script_hashes.reserve(scale(address_count.p2sh));
+    targets
+        .witness_script_hashes
+        .reserve(scale(address_count.p2wsh));
+    targets.output_keys.reserve(scale(address_count.p2tr));
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::address_file::read_addresses_file;
+    use crate::address_file::{
+        read_address_sources, read_address_sources_with_report, read_addresses_file, reserve,
+        AddressCount, AddressSource, Columns, Compression, LoadOptions, Strictness,
+    };
     use crate::btc_address;
+    use crate::btc_address::BTCAddressType;
+    use crate::error::Error;
+    use crate::network::Network;
+    use crate::targets::{Target, Targets};
+    use crate::test_util::{temp_dir, temp_file};
+    use parameterized_macro::parameterized;
 
     #[test]
     fn can_read_file() {
-        let addresses_hashes = read_addresses_file("addresses/test.txt.gz");
-        assert_eq!(1, addresses_hashes.len());
+        let targets =
+            read_addresses_file("addresses/test.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(1, targets.len());
+
+        let expected_hash = btc_address::p2pkh_address_to_160_bit_hash(
+            "127NVqnjf8gB9BFAW2dnQeM6wqmy1gbGtv",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert!(targets.pubkey_hashes.contains(&expected_hash));
+    }
+
+    #[test]
+    fn plain_list_keeps_no_target_info() {
+        let targets =
+            read_addresses_file("addresses/nested_segwit.txt.gz", &LoadOptions::default()).unwrap();
+        let stats = targets.store_stats();
+        let info_table = stats.iter().find(|s| s.name == "target info").unwrap();
+        assert_eq!(0, info_table.len);
+
+        let (target, info) = targets.iter().next().unwrap();
+        assert_eq!(None, info.balance);
+        assert!(info.address_types.contains(target.address_type()));
+        assert_eq!(targets.labels, targets.label_names(info.labels));
+    }
+
+    #[parameterized(file = {
+    "addresses/test.txt",
+    "addresses/test.txt.gz",
+    "addresses/test.txt.zst",
+    "addresses/test.txt.bz2",
+    "addresses/test.txt.xz",
+    }, compression = {
+    Compression::Plain,
+    Compression::Gzip,
+    Compression::Zstd,
+    Compression::Bzip2,
+    Compression::Xz,
+    })]
+    fn can_read_compressed_file(file: &str, compression: Compression) {
+        let header = std::fs::read(file).unwrap();
+        assert_eq!(compression, Compression::detect(&header));
+
+        let targets = read_addresses_file(file, &LoadOptions::default()).unwrap();
+        assert_eq!(1, targets.len());
+        let expected_hash = btc_address::p2pkh_address_to_160_bit_hash(
+            "127NVqnjf8gB9BFAW2dnQeM6wqmy1gbGtv",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert!(targets.pubkey_hashes.contains(&expected_hash));
+    }
+
+    #[test]
+    fn can_read_p2sh_addresses() {
+        let targets =
+            read_addresses_file("addresses/nested_segwit.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(3, targets.script_hashes.len());
+        assert!(targets.pubkey_hashes.is_empty());
+
+        let expected_hash = btc_address::p2sh_address_to_160_bit_hash(
+            "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert!(targets.script_hashes.contains(&expected_hash));
+    }
+
+    #[test]
+    fn can_read_p2tr_addresses() {
+        let targets =
+            read_addresses_file("addresses/taproot.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(4, targets.output_keys.len());
+        assert_eq!(4, targets.len());
+
+        let expected_key = btc_address::p2tr_address_to_output_key(
+            "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5",
+        )
+        .unwrap();
+        assert!(targets.output_keys.contains(&expected_key));
+    }
+
+    #[test]
+    fn can_read_p2pk_outputs() {
+        let targets =
+            read_addresses_file("addresses/p2pk.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(3, targets.len());
+        assert_eq!(1, targets.compressed_public_keys.len());
+        assert_eq!(2, targets.uncompressed_public_keys.len());
+
+        // output of the genesis block
+        let (expected_key, _) = btc_address::p2pk_output_to_public_key("04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f").unwrap();
+        assert!(targets.uncompressed_public_keys.contains(&expected_key));
+    }
+
+    #[test]
+    fn skips_invalid_addresses() {
+        let targets =
+            read_addresses_file("addresses/invalid.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(2, targets.len());
+
+        let expected_hash = btc_address::p2pkh_address_to_160_bit_hash(
+            "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert!(targets.pubkey_hashes.contains(&expected_hash));
+    }
+
+    #[test]
+    fn can_read_regtest_addresses() {
+        let targets = read_addresses_file(
+            "addresses/regtest.txt.gz",
+            &LoadOptions {
+                network: Network::Regtest,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(4, targets.len());
+        assert_eq!(1, targets.pubkey_hashes.len());
+        assert_eq!(1, targets.script_hashes.len());
+        assert_eq!(1, targets.output_keys.len());
+        assert_eq!(1, targets.compressed_public_keys.len());
+
+        let targets =
+            read_addresses_file("addresses/regtest.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(1, targets.len());
+    }
+
+    #[test]
+    fn counts_mainnet_address_on_regtest_as_invalid() {
+        let options = LoadOptions {
+            network: Network::Regtest,
+            ..Default::default()
+        };
+        let sources = [AddressSource::new("addresses/test.txt.gz")];
+        let (targets, report) = read_address_sources_with_report(&sources, &options).unwrap();
+        assert!(targets.is_empty());
+        assert_eq!(1, report.address_count.err);
+        assert_eq!(0, report.address_count.misc);
+
+        let options = LoadOptions {
+            strictness: Strictness::Fail,
+            ..options
+        };
+        let result = read_addresses_file("addresses/test.txt.gz", &options);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidLine { line: 1, source, .. })
+                if matches!(*source, Error::WrongVersion { version: 0x00, .. })
+        ));
+    }
+
+    #[test]
+    fn fails_on_invalid_address() {
+        let options = LoadOptions {
+            strictness: Strictness::Fail,
+            ..Default::default()
+        };
+        let result = read_addresses_file("addresses/invalid.txt.gz", &options);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidLine { line: 2, source, .. }) if matches!(*source, Error::InvalidChecksum(_))
+        ));
+    }
+
+    #[test]
+    fn collects_invalid_addresses() {
+        let rejects_file = temp_file("rejects.txt");
+        let options = LoadOptions {
+            strictness: Strictness::Reject(rejects_file.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let targets = read_addresses_file("addresses/invalid.txt.gz", &options).unwrap();
+        assert_eq!(2, targets.len());
+
+        let rejects = std::fs::read_to_string(&rejects_file).unwrap();
+        std::fs::remove_file(&rejects_file).unwrap();
+        let lines: Vec<&str> = rejects.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[0]
+            .starts_with("addresses/invalid.txt.gz:2\t1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZn\t"));
+        assert!(lines[1]
+            .starts_with("addresses/invalid.txt.gz:4\t3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLM\t"));
+        assert!(lines[2].starts_with("addresses/invalid.txt.gz:5\t"));
+    }
+
+    #[test]
+    fn missing_file() {
+        let result = read_addresses_file("addresses/missing.txt.gz", &LoadOptions::default());
+        assert!(matches!(result, Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn can_parse_strictness() {
+        assert_eq!(Strictness::Fail, "fail".parse().unwrap());
+        assert_eq!(Strictness::Skip, "skip".parse().unwrap());
+        assert_eq!(
+            Strictness::Reject("rejects.txt".to_string()),
+            "reject:rejects.txt".parse().unwrap()
+        );
+        assert!("reject:".parse::<Strictness>().is_err());
+    }
+
+    fn balance_options(min_balance: Option<u64>) -> LoadOptions {
+        LoadOptions {
+            columns: Columns {
+                balance: Some(1),
+                ..Default::default()
+            },
+            skip_header: true,
+            min_balance,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn can_read_balances() {
+        let targets =
+            read_addresses_file("addresses/balances.tsv.gz", &balance_options(None)).unwrap();
+        assert_eq!(3, targets.len());
+
+        let uncompressed = btc_address::p2pkh_address_to_160_bit_hash(
+            "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(5000),
+            targets
+                .get(&Target::PubkeyHash(uncompressed))
+                .unwrap()
+                .balance
+        );
+
+        // P2WPKH and P2PKH address of the same compressed key
+        let compressed = btc_address::p2pkh_address_to_160_bit_hash(
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(4000),
+            targets
+                .get(&Target::PubkeyHash(compressed))
+                .unwrap()
+                .balance
+        );
+    }
+
+    #[test]
+    fn filters_by_min_balance() {
+        let targets =
+            read_addresses_file("addresses/balances.tsv.gz", &balance_options(Some(2000))).unwrap();
+        assert_eq!(2, targets.len());
+        assert!(targets.script_hashes.is_empty());
+
+        let compressed = btc_address::p2pkh_address_to_160_bit_hash(
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(2500),
+            targets
+                .get(&Target::PubkeyHash(compressed))
+                .unwrap()
+                .balance
+        );
+    }
+
+    #[test]
+    fn keeps_balance_of_repeated_address() {
+        let dir = temp_dir("repeated");
+        // P2PKH and P2WPKH address of the same key, the P2PKH address repeated
+        let lines = "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n\
+            1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n\
+            bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t1500\n";
+        std::fs::write(dir.join("a.txt"), lines).unwrap();
+        std::fs::write(dir.join("b.txt"), lines).unwrap();
+
+        let source = AddressSource::new(dir.to_str().unwrap());
+        let options = LoadOptions {
+            skip_header: false,
+            ..balance_options(None)
+        };
+        let (targets, report) = read_address_sources_with_report(&[source], &options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let hash =
+            btc_address::hex_to_160_bit_hash("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(
+            Some(6500),
+            targets.get(&Target::PubkeyHash(hash)).unwrap().balance
+        );
+        assert_eq!(4, report.duplicates);
+    }
 
-        let expected_hash =
-            btc_address::p2pk_address_to_160_bit_hash("127NVqnjf8gB9BFAW2dnQeM6wqmy1gbGtv");
-        assert!(addresses_hashes.contains(&expected_hash));
+    #[parameterized(lines = {
+    "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n\
+        bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t1500\n\
+        bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t2000\n",
+    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t2000\n\
+        bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t1500\n\
+        1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n",
+    }, order = {"forward", "reverse"})]
+    fn balance_of_repeated_address_does_not_depend_on_order(lines: &str, order: &str) {
+        let file = temp_file(&format!("{}-order.txt", order));
+        std::fs::write(&file, lines).unwrap();
+
+        let options = LoadOptions {
+            skip_header: false,
+            ..balance_options(None)
+        };
+        let targets = read_addresses_file(file.to_str().unwrap(), &options).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let hash =
+            btc_address::hex_to_160_bit_hash("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(
+            Some(7000),
+            targets.get(&Target::PubkeyHash(hash)).unwrap().balance
+        );
+    }
+
+    #[test]
+    fn can_read_csv() {
+        let options = LoadOptions {
+            strictness: Strictness::Fail,
+            columns: Columns {
+                delimiter: ',',
+                address: 1,
+                balance: Some(2),
+            },
+            skip_header: true,
+            ..Default::default()
+        };
+        let result = read_addresses_file("addresses/balances.csv", &options);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidLine { line: 3, source, .. }) if matches!(*source, Error::InvalidBalance(_))
+        ));
+
+        let options = LoadOptions {
+            strictness: Strictness::Skip,
+            ..options
+        };
+        let targets = read_addresses_file("addresses/balances.csv", &options).unwrap();
+        assert_eq!(1, targets.len());
+    }
+
+    #[test]
+    fn can_read_descriptors() {
+        let options = LoadOptions {
+            network: Network::Regtest,
+            ..Default::default()
+        };
+        let source: AddressSource = "drill=addresses/descriptors.txt".parse().unwrap();
+        let (targets, report) = read_address_sources_with_report(&[source], &options).unwrap();
+
+        assert_eq!(5, targets.len());
+        assert_eq!(vec!["drill"], targets.labels);
+        let count = &report.address_count;
+        assert_eq!(
+            (2, 1, 1, 1, 1, 1),
+            (
+                count.p2pkh,
+                count.p2wpkh,
+                count.p2sh,
+                count.p2wsh,
+                count.p2tr,
+                count.err
+            )
+        );
+        // the checksum of the last line does not match
+        assert_eq!(7, report.invalid_lines[0].line_number);
+        assert!(matches!(
+            report.invalid_lines[0].error,
+            Error::InvalidDescriptor { .. }
+        ));
+
+        let hash =
+            btc_address::hex_to_160_bit_hash("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let address_types = targets
+            .get(&Target::PubkeyHash(hash))
+            .unwrap()
+            .address_types;
+        assert!(address_types.contains(BTCAddressType::P2PKH));
+        assert!(address_types.contains(BTCAddressType::P2WPKH));
+    }
+
+    #[test]
+    fn can_read_tagged_hashes() {
+        let targets = read_addresses_file("addresses/tagged.txt", &LoadOptions::default()).unwrap();
+        assert_eq!(6, targets.len());
+        assert_eq!(2, targets.pubkey_hashes.len());
+        assert_eq!(1, targets.script_hashes.len());
+        assert_eq!(1, targets.witness_script_hashes.len());
+        assert_eq!(1, targets.output_keys.len());
+        assert_eq!(1, targets.compressed_public_keys.len());
+
+        // p2wpkh:<hex> and the P2WPKH address of the same hash are one target
+        let expected_hash = btc_address::p2wpkh_address_to_160_bit_hash(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+        )
+        .unwrap();
+        assert!(targets.pubkey_hashes.contains(&expected_hash));
+        let expected_hash = btc_address::p2sh_address_to_160_bit_hash(
+            "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN",
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert!(targets.script_hashes.contains(&expected_hash));
+    }
+
+    #[parameterized(bytes_read = {1_000, 1_000, 0, 10_000}, file_size = {
+    Some(10_000), None, Some(10_000), Some(10_000),
+    }, pubkey_hashes = {13_500, 0, 0, 0}, script_hashes = {1_800, 0, 0, 0})]
+    fn reserves_capacity_for_rest_of_file(
+        bytes_read: u64,
+        file_size: Option<u64>,
+        pubkey_hashes: usize,
+        script_hashes: usize,
+    ) {
+        let address_count = AddressCount {
+            p2pkh: 1_000,
+            p2wpkh: 500,
+            p2sh: 200,
+            ..Default::default()
+        };
+        let mut targets = Targets::new();
+        reserve(&mut targets, &address_count, bytes_read, file_size);
+
+        // the hash sets round the capacity up, to less than twice of what was reserved
+        let reserved =
+            |capacity: usize, expected: usize| capacity >= expected && capacity <= 2 * expected;
+        assert!(reserved(targets.pubkey_hashes.capacity(), pubkey_hashes));
+        assert!(reserved(targets.script_hashes.capacity(), script_hashes));
+        assert_eq!(0, targets.witness_script_hashes.capacity());
+        assert_eq!(0, targets.output_keys.capacity());
+    }
+
+    #[test]
+    fn reads_large_file_in_order() {
+        let file = temp_file("large.txt");
+        let lines: Vec<String> = (1..=40_000u32)
+            .map(|i| match i {
+                30_000 | 35_000 => format!("p2pkh:{:038x}", i),
+                _ => format!("p2pkh:{:040x}", i),
+            })
+            .collect();
+        std::fs::write(&file, lines.join("\n")).unwrap();
+        let file_name = file.to_str().unwrap();
+
+        let options = LoadOptions {
+            threads: 4,
+            ..Default::default()
+        };
+        let targets = read_addresses_file(file_name, &options).unwrap();
+        assert_eq!(39_998, targets.pubkey_hashes.len());
+
+        let options = LoadOptions {
+            strictness: Strictness::Fail,
+            ..options
+        };
+        let result = read_addresses_file(file_name, &options);
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            result,
+            Err(Error::InvalidLine { line: 30_000, .. })
+        ));
+    }
+
+    #[test]
+    fn can_parse_address_source() {
+        assert_eq!(
+            AddressSource::new("addresses/top_10.txt.gz"),
+            "addresses/top_10.txt.gz".parse().unwrap()
+        );
+        assert_eq!(
+            AddressSource {
+                label: Some("regtest-drill".to_string()),
+                path: "addresses/regtest.txt.gz".to_string(),
+            },
+            "regtest-drill=addresses/regtest.txt.gz".parse().unwrap()
+        );
+        assert!("=addresses/regtest.txt.gz"
+            .parse::<AddressSource>()
+            .is_err());
+        assert!("drill=".parse::<AddressSource>().is_err());
+    }
+
+    #[test]
+    fn labels_targets_of_each_file() {
+        let sources = [
+            "drill=addresses/tagged.txt".parse().unwrap(),
+            AddressSource::new("addresses/p2pk.txt.gz"),
+        ];
+        let targets = read_address_sources(&sources, &LoadOptions::default()).unwrap();
+        assert_eq!(vec!["drill", "p2pk"], targets.labels);
+
+        // key 1 compressed is listed in both files
+        let (key_1, _) = btc_address::p2pk_output_to_public_key(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let info = targets.get(&Target::PublicKey(key_1, true)).unwrap();
+        assert_eq!(vec!["drill", "p2pk"], targets.label_names(info.labels));
+
+        let hash =
+            btc_address::hex_to_160_bit_hash("bcfeb728b584253d5f3f70bcb780e9ef218a68f4").unwrap();
+        let info = targets.get(&Target::ScriptHash(hash)).unwrap();
+        assert_eq!(vec!["drill"], targets.label_names(info.labels));
+    }
+
+    #[test]
+    fn can_read_directory() {
+        let dir = temp_dir("dir");
+        std::fs::copy("addresses/test.txt.gz", dir.join("b.txt.gz")).unwrap();
+        std::fs::copy("addresses/nested_segwit.txt.gz", dir.join("a.txt.gz")).unwrap();
+        let path = dir.to_str().unwrap().to_string();
+
+        let files = AddressSource::new(&path).files().unwrap();
+        let labels: Vec<&str> = files.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(vec!["a", "b"], labels);
+
+        let source = AddressSource {
+            label: Some("puzzle".to_string()),
+            path,
+        };
+        let targets = read_address_sources(&[source], &LoadOptions::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(4, targets.len());
+        assert_eq!(vec!["puzzle"], targets.labels);
+    }
+
+    #[test]
+    fn can_read_utxo_snapshot() {
+        let options = LoadOptions {
+            network: Network::Regtest,
+            ..Default::default()
+        };
+        let targets = read_addresses_file("addresses/utxo_snapshot.dat", &options).unwrap();
+        assert_eq!(6, targets.len());
+        assert_eq!(vec!["utxo_snapshot"], targets.labels);
+
+        let hash =
+            btc_address::hex_to_160_bit_hash("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let info = targets.get(&Target::PubkeyHash(hash)).unwrap();
+        assert_eq!(Some(5_000_001_000), info.balance);
+        assert!(info.address_types.contains(BTCAddressType::P2PKH));
+        assert!(info.address_types.contains(BTCAddressType::P2WPKH));
+        assert_eq!(1, targets.uncompressed_public_keys.len());
+
+        let options = LoadOptions {
+            min_balance: Some(2500),
+            ..options
+        };
+        let targets = read_addresses_file("addresses/utxo_snapshot.dat", &options).unwrap();
+        assert_eq!(5, targets.len());
+        assert_eq!(
+            Some(5_000_001_000),
+            targets.get(&Target::PubkeyHash(hash)).unwrap().balance
+        );
+        assert!(targets.script_hashes.is_empty());
+
+        // the minimum applies to the sum of the coins of a target, not to each coin
+        let options = LoadOptions {
+            min_balance: Some(5_000_000_500),
+            ..options
+        };
+        let targets = read_addresses_file("addresses/utxo_snapshot.dat", &options).unwrap();
+        assert_eq!(
+            Some(5_000_001_000),
+            targets.get(&Target::PubkeyHash(hash)).unwrap().balance
+        );
     }
 }
@@ -1,18 +1,35 @@
 use btc_collider_rs::address_file;
+use btc_collider_rs::address_file::{AddressSource, Columns, LoadOptions, Strictness};
+use btc_collider_rs::block_file;
 use btc_collider_rs::collider::Collider;
-use btc_collider_rs::search_space::file_search_space_provider::FileSearchSpaceProvider;
-use btc_collider_rs::search_space::puzzle_search_space_provider::PuzzleSearchSpaceProvider;
-use btc_collider_rs::search_space::random_search_space_provider::RandomSearchSpaceProvider;
-use btc_collider_rs::search_space::SearchSpaceProvider;
+use btc_collider_rs::delta;
+use btc_collider_rs::delta::DeltaWatcher;
+use btc_collider_rs::harvest;
+use btc_collider_rs::inspect;
+use btc_collider_rs::network::Network;
+use btc_collider_rs::normalize;
+use btc_collider_rs::script_template::ScriptTemplate;
+use btc_collider_rs::search_space::file_search_space_provider::{
+    ScanDirection, ScanOptions, StartPolicy,
+};
+use btc_collider_rs::search_space::provider_spec::{ProviderOptions, ProviderSpec};
+use btc_collider_rs::search_space::puzzle_search_space_provider;
+use btc_collider_rs::search_space::shard::{Shard, ShardStrategy};
+use btc_collider_rs::search_space::weighted_search_space_provider::{
+    read_mix_file, WeightedSearchSpaceProvider, WeightedSpec,
+};
+use btc_collider_rs::search_space::{SearchSpace, SearchSpaceProvider};
+use btc_collider_rs::session::Session;
+use btc_collider_rs::targets::Targets;
 use btc_collider_rs::wif::private_key_to_wif;
 use chrono::{DateTime, Utc};
-use clap::Parser;
-use log::{debug, info, LevelFilter};
-use primitive_types::H160;
+use clap::{Args, Parser, Subcommand};
+use log::{debug, error, info, LevelFilter};
 use secp256k1::{All, Secp256k1};
 use simplelog::{ColorChoice, CombinedLogger, Config, TermLogger, TerminalMode, WriteLogger};
-use std::collections::HashSet;
+use std::fmt::Display;
 use std::fs::File;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread;
@@ -20,15 +37,215 @@ use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
-struct Args {
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    run: RunArgs,
+
+    /// Directory holding the sessions
+    #[clap(long, global = true, default_value = "sessions")]
+    sessions_dir: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage named sessions
+    #[clap(subcommand)]
+    Sessions(SessionsCommand),
+    /// Load address files and print what they hold without starting a search
+    Inspect(AddressArgs),
+    /// Write the targets of raw block files of a node to an address file
+    ScanBlocks {
+        /// blk*.dat files or block directories, e.g. ~/.bitcoin/regtest/blocks
+        #[clap(required = true)]
+        blocks: Vec<PathBuf>,
+
+        /// Address file to write, gzip compressed if the name ends with .gz
+        #[clap(short, long)]
+        output: String,
+
+        /// Network of the blocks: mainnet, testnet, signet or regtest
+        #[clap(long, default_value = "mainnet")]
+        network: Network,
+    },
+    /// Write the public keys that raw block files reveal for hash targets to an address file
+    HarvestPubkeys {
+        /// blk*.dat files or block directories, e.g. ~/.bitcoin/blocks
+        #[clap(required = true)]
+        blocks: Vec<PathBuf>,
+
+        /// Address file of p2pk lines to write, gzip compressed if the name ends with .gz
+        #[clap(short, long)]
+        output: String,
+
+        #[clap(flatten)]
+        address: AddressArgs,
+    },
+    /// Write the delta file that turns the targets of --previous into those of --addresses
+    Delta {
+        /// Address files or directories of the previous target list, read like --addresses
+        #[clap(long, required = true)]
+        previous: Vec<AddressSource>,
+
+        /// Delta file to write, gzip compressed if the name ends with .gz
+        #[clap(short, long)]
+        output: String,
+
+        #[clap(flatten)]
+        address: AddressArgs,
+    },
+    /// Write the targets of --addresses deduplicated, canonical and sorted by type, with a
+    /// rejects file of the invalid lines and a manifest of counts and the content hash
+    Normalize {
+        /// Address file to write, gzip compressed if the name ends with .gz
+        #[clap(short, long)]
+        output: String,
+
+        /// File to write invalid lines to, by default <output>.rejects.txt without .txt.gz
+        #[clap(long)]
+        rejects: Option<String>,
+
+        /// Manifest file to write, by default <output>.manifest.txt without .txt.gz
+        #[clap(long)]
+        manifest: Option<String>,
+
+        #[clap(flatten)]
+        address: AddressArgs,
+    },
+}
+
+/// Where the targets are read from and how.
+#[derive(Args, Debug)]
+struct AddressArgs {
+    /// Network the addresses and WIFs are encoded for: mainnet, testnet, signet or regtest
+    #[clap(long, default_value = "mainnet")]
+    network: Network,
+
+    /// Address file or directory of address files, plain or compressed with gzip, zstd, bzip2
+    /// or xz. Use - for stdin. Prefix with <label>= to label its targets, otherwise they are
+    /// labelled with the file name. Can be given multiple times
+    #[clap(long, default_value = "addresses/latest.txt.gz")]
+    addresses: Vec<AddressSource>,
+
+    /// Column delimiter of address tables: tab, comma or any single character
+    #[clap(long, default_value = "tab", value_parser = parse_delimiter)]
+    delimiter: char,
+
+    /// Index of the address column, starting at 0
+    #[clap(long, default_value_t = 0)]
+    address_column: usize,
+
+    /// Index of the balance column in satoshis, starting at 0
+    #[clap(long)]
+    balance_column: Option<usize>,
+
+    /// Skip the header row of the address file
+    #[clap(long)]
+    skip_header: bool,
+
+    /// Only load addresses with at least this balance in satoshis
+    #[clap(long, requires = "balance_column")]
+    min_balance: Option<u64>,
+
+    /// What to do with invalid lines in the address file: fail, skip or reject:<file>
+    #[clap(long, default_value = "skip")]
+    strictness: Strictness,
+}
+
+impl AddressArgs {
+    fn load_options(&self, threads: usize) -> LoadOptions {
+        LoadOptions {
+            network: self.network,
+            strictness: self.strictness.clone(),
+            columns: Columns {
+                delimiter: self.delimiter,
+                address: self.address_column,
+                balance: self.balance_column,
+            },
+            skip_header: self.skip_header,
+            min_balance: self.min_balance,
+            threads,
+            reject_unmatched: false,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionsCommand {
+    /// List all sessions that are not archived
+    List,
+    /// Continue a session with the arguments it was started with
+    Resume { name: String },
+    /// Move a session into the archive
+    Archive { name: String },
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Keep progress, results and logs in the named session
+    #[clap(short, long)]
+    session: Option<String>,
+
     /// Search randomly
     #[clap(short, long)]
     random: bool,
 
     /// Search puzzle
-    #[clap(short, long)]
+    #[clap(short, long, value_parser = puzzle_search_space_provider::parse_puzzle_number)]
     puzzle: Option<usize>,
 
+    /// Search range <start>-<end> (hex)
+    #[clap(long)]
+    range: Option<SearchSpace>,
+
+    /// Mix providers by weight, e.g. --mix 70=puzzle:69 --mix 30=random
+    #[clap(long)]
+    mix: Vec<WeightedSpec>,
+
+    /// Read the provider mix from a file with one <weight>=<provider> per line
+    #[clap(long)]
+    mix_file: Option<String>,
+
+    /// Only search the share <index>/<count> of the range, e.g. 3/8
+    #[clap(long)]
+    shard: Option<Shard>,
+
+    /// How to split the range into shards (interleaved or contiguous)
+    #[clap(long, default_value = "interleaved")]
+    shard_strategy: ShardStrategy,
+
+    /// Where the default mode starts without done search spaces: random, range-start or a hex key
+    #[clap(long, default_value = "random")]
+    scan_start: StartPolicy,
+
+    /// Direction the default mode extends the done search spaces: up, down or both
+    #[clap(long, default_value = "up")]
+    scan_direction: ScanDirection,
+
+    /// Do not search the holes between done search spaces before extending them
+    #[clap(long)]
+    no_fill_gaps: bool,
+
+    #[clap(flatten)]
+    address: AddressArgs,
+
+    /// Delta file or directory of delta files, applied in name order after loading the addresses.
+    /// Can be given multiple times
+    #[clap(long)]
+    delta: Vec<AddressSource>,
+
+    /// Apply the delta files in this directory after loading, and new ones while searching
+    #[clap(long)]
+    watch_deltas: Option<AddressSource>,
+
+    /// Script over the candidate public key to match against P2SH and P2WSH targets: sh(pk),
+    /// sh(pkh), sh(wpkh), wsh(pk), wsh(pkh), sh(wsh(pk)) or sh(wsh(pkh)). Can be given multiple
+    /// times, replacing the default
+    #[clap(long, default_value = "sh(wpkh)")]
+    script_template: Vec<ScriptTemplate>,
+
     /// Number of threads
     #[clap(long, default_value_t = num_cpus::get())]
     threads: usize,
@@ -39,41 +256,293 @@ struct Args {
 }
 
 fn main() {
-    init_logging();
+    let cli = Cli::parse();
+    let sessions_dir = cli.sessions_dir;
+
+    match cli.command {
+        Some(Command::Inspect(args)) => {
+            let inspection = inspect::inspect(&args.addresses, &args.load_options(0))
+                .unwrap_or_else(|e| exit_with(e));
+            print!("{}", inspection);
+        }
+        Some(Command::ScanBlocks {
+            blocks,
+            output,
+            network,
+        }) => {
+            init_logging("log".to_string());
+            let count = block_file::scan_block_files(&block_files(&blocks), network, &output)
+                .unwrap_or_else(|e| exit_with(e));
+            println!(
+                "Wrote {} targets of {} blocks, {} transactions, {} outputs and {} revealed public keys to {}",
+                count.targets,
+                count.blocks,
+                count.transactions,
+                count.outputs,
+                count.revealed_public_keys,
+                output
+            );
+        }
+        Some(Command::HarvestPubkeys {
+            blocks,
+            output,
+            address,
+        }) => {
+            init_logging("log".to_string());
+            let targets =
+                address_file::read_address_sources(&address.addresses, &address.load_options(0))
+                    .unwrap_or_else(|e| exit_with(e));
+            let count = harvest::harvest_public_keys(
+                &targets,
+                &block_files(&blocks),
+                address.network,
+                &output,
+            )
+            .unwrap_or_else(|e| exit_with(e));
+            println!(
+                "Wrote public keys of {} targets to {}, {} targets have no revealed public key",
+                count.paired_targets, output, count.unpaired_targets
+            );
+        }
+        Some(Command::Delta {
+            previous,
+            output,
+            address,
+        }) => {
+            let options = address.load_options(0);
+            let old = address_file::read_address_sources(&previous, &options)
+                .unwrap_or_else(|e| exit_with(e));
+            let new = address_file::read_address_sources(&address.addresses, &options)
+                .unwrap_or_else(|e| exit_with(e));
+            let count = delta::write_delta(&old, &new, &output).unwrap_or_else(|e| exit_with(e));
+            println!(
+                "Wrote {} added, {} updated and {} removed targets to {}",
+                count.added, count.updated, count.removed, output
+            );
+        }
+        Some(Command::Normalize {
+            output,
+            rejects,
+            manifest,
+            address,
+        }) => {
+            let stem = normalize::output_stem(&output);
+            let rejects = rejects.unwrap_or_else(|| format!("{}.rejects.txt", stem));
+            let manifest_file = manifest.unwrap_or_else(|| format!("{}.manifest.txt", stem));
+            let manifest = normalize::normalize(
+                &address.addresses,
+                &address.load_options(0),
+                &output,
+                &rejects,
+            )
+            .unwrap_or_else(|e| exit_with(e));
+            manifest
+                .write(&manifest_file)
+                .unwrap_or_else(|e| exit_with(e));
+            print!("{}", manifest);
+        }
+        Some(Command::Sessions(SessionsCommand::List)) => {
+            for name in Session::list(&sessions_dir).unwrap_or_else(|e| exit_with(e)) {
+                println!("{}", name);
+            }
+        }
+        Some(Command::Sessions(SessionsCommand::Archive { name })) => {
+            let session = Session::existing(&sessions_dir, &name).unwrap_or_else(|e| exit_with(e));
+            let archived = session
+                .archive(&sessions_dir)
+                .unwrap_or_else(|e| exit_with(e));
+            println!("Archived session {} to {}", name, archived.display());
+        }
+        Some(Command::Sessions(SessionsCommand::Resume { name })) => {
+            let session = Session::existing(&sessions_dir, &name).unwrap_or_else(|e| exit_with(e));
+            let config = session.read_config().unwrap_or_else(|e| exit_with(e));
+            let cli = Cli::parse_from(std::iter::once("btc-collider-rs".to_string()).chain(config));
+            run(cli.run, Some(session));
+        }
+        None => {
+            let session = cli.run.session.as_ref().map(|name| {
+                let session = Session::open(&sessions_dir, name).unwrap_or_else(|e| exit_with(e));
+                let args: Vec<String> = std::env::args().skip(1).collect();
+                session.write_config(&args).unwrap_or_else(|e| exit_with(e));
+                session
+            });
+            run(cli.run, session);
+        }
+    }
+}
+
+fn parse_delimiter(s: &str) -> Result<char, String> {
+    match s {
+        "tab" | "\\t" => Ok('\t'),
+        "comma" => Ok(','),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(format!("'{}' is not a single character", s)),
+            }
+        }
+    }
+}
+
+/// The block files of the given files and block directories.
+fn block_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        files.extend(block_file::block_files(path).unwrap_or_else(|e| exit_with(e)));
+    }
+    files
+}
+
+fn exit_with<E: Display>(e: E) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1);
+}
+
+fn run(args: RunArgs, session: Option<Session>) {
+    init_logging(session.as_ref().map_or("log".to_string(), |s| s.log_dir()));
 
-    let args = Args::parse();
     let random = args.random;
     let puzzle = args.puzzle;
+    let shard = args
+        .shard
+        .unwrap_or_default()
+        .with_strategy(args.shard_strategy);
 
     info!("Start btc-collider-rs");
+    if let Some(session) = &session {
+        info!("Session {}", session.name());
+    }
+    if args.address.network != Network::Mainnet {
+        info!("Network {}", args.address.network);
+    }
+    if shard.is_sharded() {
+        info!("Shard {}", shard);
+    }
+
+    let mut mix = args.mix;
+    if let Some(mix_file) = args.mix_file {
+        match read_mix_file(&mix_file) {
+            Ok(specs) => mix.extend(specs),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
-    let search_space_provider: Box<dyn SearchSpaceProvider> = if random {
-        info!("Mode random");
-        Box::new(RandomSearchSpaceProvider::new())
-    } else if puzzle.is_some() {
-        info!("Mode puzzle #{}", puzzle.unwrap());
-        Box::new(PuzzleSearchSpaceProvider::new(puzzle.unwrap()))
+    let mut options = ProviderOptions {
+        shard,
+        scan: ScanOptions {
+            start: args.scan_start,
+            direction: args.scan_direction,
+            fill_gaps: !args.no_fill_gaps,
+        },
+        ..Default::default()
+    };
+    if let Some(session) = &session {
+        options.done_file = session.done_file();
+    }
+
+    let search_space_provider: Result<Box<dyn SearchSpaceProvider>, String> = if !mix.is_empty() {
+        info!(
+            "Mode mix {}",
+            mix.iter()
+                .map(|spec| spec.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        WeightedSearchSpaceProvider::from_specs(&mix, &options)
+            .map(|p| Box::new(p) as Box<dyn SearchSpaceProvider>)
     } else {
-        info!("Mode default");
-        Box::new(FileSearchSpaceProvider::new("searchspace/done.txt"))
+        let spec = if random {
+            info!("Mode random");
+            ProviderSpec::Random
+        } else if puzzle.is_some() {
+            info!("Mode puzzle #{}", puzzle.unwrap());
+            ProviderSpec::Puzzle(puzzle.unwrap())
+        } else if let Some(range) = args.range {
+            info!("Mode range {}", range);
+            ProviderSpec::Range(range)
+        } else {
+            info!("Mode default");
+            ProviderSpec::File
+        };
+        spec.build(&options).map_err(|e| e.to_string())
+    };
+    let search_space_provider = match search_space_provider {
+        Ok(search_space_provider) => search_space_provider,
+        Err(e) => {
+            error!("Cannot load search space: {}", e);
+            std::process::exit(1);
+        }
     };
 
-    let hashes = address_file::read_addresses_file("addresses/latest.txt.gz");
-    let hashes = Arc::new(RwLock::new(hashes));
+    let load_options = args.address.load_options(args.threads);
+    let mut targets =
+        match address_file::read_address_sources(&args.address.addresses, &load_options) {
+            Ok(targets) => targets,
+            Err(e) => {
+                error!("Cannot load addresses: {}", e);
+                std::process::exit(1);
+            }
+        };
+    if let Err(e) = delta::apply_deltas(&mut targets, &args.delta, &load_options) {
+        error!("Cannot apply delta: {}", e);
+        std::process::exit(1);
+    }
+    let targets = Arc::new(RwLock::new(targets));
+    let continue_search = Arc::new(AtomicBool::new(true));
+    if let Some(deltas) = args.watch_deltas {
+        let mut watcher = DeltaWatcher::new(deltas, load_options.clone());
+        if let Err(e) = watcher.poll(&targets) {
+            error!("Cannot apply delta: {}", e);
+            std::process::exit(1);
+        }
+        let targets = targets.clone();
+        let continue_search = continue_search.clone();
+        thread::spawn(move || {
+            while continue_search.load(Ordering::Relaxed) {
+                thread::sleep(delta::POLL_INTERVAL);
+                if let Err(e) = watcher.poll(&targets) {
+                    error!("Cannot apply delta: {}", e);
+                }
+            }
+        });
+    }
     let secp = Arc::new(RwLock::new(Secp256k1::new()));
 
     let search_space_provider = Arc::new(RwLock::new(search_space_provider));
+    let session = Arc::new(session);
     let mut thread_handles = Vec::new();
 
+    info!(
+        "Script templates {}",
+        args.script_template
+            .iter()
+            .map(|template| template.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
     info!("Start collider on {} threads", args.threads);
-    let continue_search = Arc::new(AtomicBool::new(true));
     for _ in 0..args.threads {
-        let hashes = hashes.clone();
+        let targets = targets.clone();
         let secp = secp.clone();
         let search_space_provider = search_space_provider.clone();
         let continue_search = continue_search.clone();
+        let session = session.clone();
+        let network = args.address.network;
+        let script_templates = args.script_template.clone();
         thread_handles.push(thread::spawn(move || {
-            run_search(hashes, secp, search_space_provider, continue_search);
+            run_search(
+                targets,
+                secp,
+                search_space_provider,
+                continue_search,
+                session,
+                network,
+                script_templates,
+            );
         }));
     }
 
@@ -99,49 +568,62 @@ fn main() {
 }
 
 fn run_search(
-    hashes: Arc<RwLock<HashSet<H160>>>,
+    targets: Arc<RwLock<Targets>>,
     secp: Arc<RwLock<Secp256k1<All>>>,
     search_space_provider: Arc<RwLock<Box<dyn SearchSpaceProvider>>>,
     continue_search: Arc<AtomicBool>,
+    session: Arc<Option<Session>>,
+    network: Network,
+    script_templates: Vec<ScriptTemplate>,
 ) {
-    let hashes = hashes.read().unwrap();
-
-    let collider = Collider {
-        addresses: &hashes,
-        secp: &secp.read().unwrap(),
-    };
+    let secp = secp.read().unwrap();
 
     while continue_search.load(Ordering::Relaxed) {
-        let search_space = search_space_provider.write().unwrap().next();
+        let issued = search_space_provider.write().unwrap().next();
 
-        let result = collider.run(search_space);
+        // the targets are locked per search space, so that deltas can be applied in between
+        let result = Collider {
+            targets: &targets.read().unwrap(),
+            secp: &secp,
+            network,
+            script_templates: &script_templates,
+        }
+        .run(issued.search_space.clone());
 
-        search_space_provider
-            .write()
-            .unwrap()
-            .done(&result.search_space);
+        search_space_provider.write().unwrap().done(&issued);
 
         for found_key in result.found_keys {
-            info!(
-                "Collision found for {:?}, {}. Key {} in {}. WIF {}",
+            let report = format!(
+                "Collision found for {:?}, {:?}, {}{}{}. Key {} in {}. WIF {}. {}",
                 found_key.strategy,
+                found_key.encoding,
                 if found_key.compressed {
                     "compressed"
                 } else {
                     "uncompressed"
                 },
+                found_key.balance_info(),
+                found_key.label_info(),
                 found_key.key.to_str_radix(16),
                 result.search_space,
-                private_key_to_wif(&found_key.key, found_key.compressed)
+                private_key_to_wif(&found_key.key, found_key.compressed, network),
+                found_key.address_info(network)
             );
+            info!("{}", report);
+
+            if let Some(session) = session.as_ref() {
+                if let Err(e) = session.append_result(&report) {
+                    error!("Cannot write to {}: {}", session.results_file(), e);
+                }
+            }
         }
     }
     debug!("Thread done");
 }
 
-fn init_logging() {
+fn init_logging(log_dir: String) {
     let now: DateTime<Utc> = Utc::now();
-    let log_file = format!("log/{}.log", now.format("%Y-%m-%dT%H%M%S"));
+    let log_file = format!("{}/{}.log", log_dir, now.format("%Y-%m-%dT%H%M%S"));
 
     CombinedLogger::init(vec![
         TermLogger::new(
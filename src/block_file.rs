@@ -0,0 +1,454 @@
+use crate::btc_address::BTCAddressType;
+use crate::error::Error;
+use crate::network::Network;
+use crate::script;
+use crate::serialize::{read_bytes, read_compact_size, read_u32, read_u64, read_vec};
+use crate::{address_file, btc_address};
+use log::info;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Largest block Bitcoin Core accepts, in bytes
+const MAX_BLOCK_SERIALIZED_SIZE: u32 = 4_000_000;
+/// Key Bitcoin Core 28 and later obfuscates the block files of a data directory with
+const XOR_KEY_FILE: &str = "xor.dat";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxIn {
+    pub script_sig: Vec<u8>,
+    pub witness: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxOut {
+    /// Amount in satoshis
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+}
+
+/// Reads the blocks of a blk*.dat file of Bitcoin Core: every block is stored behind the message
+/// start of the network and its size, unused space at the end of a file is zero.
+pub struct BlockFileReader<R> {
+    reader: R,
+    file_name: String,
+    network: Network,
+    blocks_read: u64,
+    done: bool,
+}
+
+impl<R: Read> BlockFileReader<R> {
+    pub fn new(reader: R, file_name: &str, network: Network) -> Self {
+        BlockFileReader {
+            reader,
+            file_name: file_name.to_string(),
+            network,
+            blocks_read: 0,
+            done: false,
+        }
+    }
+
+    /// The transactions of the next block, `None` at the end of the file.
+    fn read_block(&mut self) -> Result<Option<Vec<Transaction>>, Error> {
+        let message_start: [u8; 4] = match read_bytes(&mut self.reader) {
+            Ok(message_start) => message_start,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(Error::io(&self.file_name, e)),
+        };
+        if message_start == [0; 4] {
+            return Ok(None);
+        }
+        if !self.network.message_starts().contains(&message_start) {
+            return Err(self.invalid(format!("not a block of {}", self.network)));
+        }
+
+        let size = read_u32(&mut self.reader).map_err(|e| Error::io(&self.file_name, e))?;
+        if size > MAX_BLOCK_SERIALIZED_SIZE {
+            return Err(self.invalid(format!("size {} exceeds the maximum block size", size)));
+        }
+        let block = read_vec(&mut self.reader, size as usize).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => self.invalid("truncated block"),
+            _ => Error::io(&self.file_name, e),
+        })?;
+        let transactions = parse_block(&block).map_err(|e| self.invalid(e.to_string()))?;
+        Ok(Some(transactions))
+    }
+
+    fn invalid(&self, reason: impl Display) -> Error {
+        Error::invalid_data(
+            &self.file_name,
+            format!("block {}: {}", self.blocks_read, reason),
+        )
+    }
+}
+
+impl<R: Read> Iterator for BlockFileReader<R> {
+    type Item = Result<Vec<Transaction>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let block = self.read_block();
+        match block {
+            Ok(Some(_)) => self.blocks_read += 1,
+            _ => self.done = true,
+        }
+        block.transpose()
+    }
+}
+
+fn parse_block(mut block: &[u8]) -> io::Result<Vec<Transaction>> {
+    let _header: [u8; 80] = read_bytes(&mut block)?;
+    let count = read_compact_size(&mut block)?;
+    let transactions = (0..count)
+        .map(|_| read_transaction(&mut block))
+        .collect::<io::Result<Vec<_>>>()?;
+    if !block.is_empty() {
+        return Err(invalid_data("data after the last transaction"));
+    }
+    Ok(transactions)
+}
+
+/// Reads a transaction, with the witnesses of its inputs if it has the segwit marker.
+fn read_transaction(reader: &mut &[u8]) -> io::Result<Transaction> {
+    let _version = read_u32(reader)?;
+    let mut input_count = read_compact_size(reader)?;
+    let segwit = input_count == 0;
+    if segwit {
+        let [flag] = read_bytes(reader)?;
+        if flag != 1 {
+            return Err(invalid_data("unknown transaction flag"));
+        }
+        input_count = read_compact_size(reader)?;
+    }
+
+    let mut inputs = Vec::new();
+    for _ in 0..input_count {
+        let _prevout: [u8; 36] = read_bytes(reader)?;
+        let script_sig = read_var_bytes(reader)?;
+        let _sequence = read_u32(reader)?;
+        inputs.push(TxIn {
+            script_sig,
+            witness: Vec::new(),
+        });
+    }
+    let mut outputs = Vec::new();
+    for _ in 0..read_compact_size(reader)? {
+        outputs.push(TxOut {
+            value: read_u64(reader)?,
+            script_pubkey: read_var_bytes(reader)?,
+        });
+    }
+    if segwit {
+        for input in &mut inputs {
+            for _ in 0..read_compact_size(reader)? {
+                input.witness.push(read_var_bytes(reader)?);
+            }
+        }
+    }
+    let _lock_time = read_u32(reader)?;
+    Ok(Transaction { inputs, outputs })
+}
+
+fn read_var_bytes(reader: &mut &[u8]) -> io::Result<Vec<u8>> {
+    let len = read_compact_size(reader)?;
+    if len > reader.len() as u64 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    read_vec(reader, len as usize)
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// Undoes the obfuscation of block files with the repeated key of `xor.dat`.
+struct XorReader<R> {
+    reader: R,
+    key: [u8; 8],
+    position: usize,
+}
+
+impl<R: Read> Read for XorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        for byte in &mut buf[..n] {
+            *byte ^= self.key[self.position % self.key.len()];
+            self.position += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// Opens a block file, deobfuscated with the `xor.dat` key of its directory if there is one.
+pub fn open_block_file(path: &Path) -> Result<Box<dyn Read>, Error> {
+    let file_name = path.to_string_lossy();
+    let reader = BufReader::new(File::open(path).map_err(|e| Error::io(&file_name, e))?);
+    let key_file = path.with_file_name(XOR_KEY_FILE);
+    let key = match fs::read(&key_file) {
+        Ok(key) => key,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Box::new(reader)),
+        Err(e) => return Err(Error::io(&key_file.to_string_lossy(), e)),
+    };
+    match <[u8; 8]>::try_from(key.as_slice()) {
+        Ok([0, 0, 0, 0, 0, 0, 0, 0]) => Ok(Box::new(reader)),
+        Ok(key) => Ok(Box::new(XorReader {
+            reader,
+            key,
+            position: 0,
+        })),
+        Err(_) => Err(Error::invalid_data(
+            &key_file.to_string_lossy(),
+            "not an 8-byte key",
+        )),
+    }
+}
+
+/// The blk*.dat files of a directory in order, or the given file.
+pub fn block_files(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let path_name = path.to_string_lossy();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| Error::io(&path_name, e))? {
+        let file = entry.map_err(|e| Error::io(&path_name, e))?.path();
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with("blk") && name.ends_with(".dat") {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// What a block file scan found.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanCount {
+    pub blocks: u64,
+    pub transactions: u64,
+    pub outputs: u64,
+    pub revealed_public_keys: u64,
+    /// Distinct targets written to the address file
+    pub targets: u64,
+}
+
+/// Writes the targets of all outputs and the public keys revealed by inputs of the block files to
+/// an address file, each target once as type tagged hex. The file is gzip compressed if its name
+/// ends with `.gz`.
+pub fn scan_block_files(
+    files: &[PathBuf],
+    network: Network,
+    output: &str,
+) -> Result<ScanCount, Error> {
+    address_file::create_address_file(output, |writer| scan_into(files, network, writer, output))
+}
+
+fn scan_into(
+    files: &[PathBuf],
+    network: Network,
+    writer: &mut dyn Write,
+    output: &str,
+) -> Result<ScanCount, Error> {
+    let mut count = ScanCount::default();
+    let mut written = WrittenPrograms::default();
+    let mut write = |address_type, program: &[u8], count: &mut ScanCount| {
+        let Some(line) = btc_address::type_tagged(address_type, program) else {
+            return Ok(());
+        };
+        if !written.insert(address_type, program) {
+            return Ok(());
+        }
+        writeln!(writer, "{}", line).map_err(|e| Error::io(output, e))?;
+        count.targets += 1;
+        Ok(())
+    };
+
+    count.blocks = read_block_files(files, network, |transaction| {
+        count.transactions += 1;
+        for input in &transaction.inputs {
+            if let Some(key) = script::revealed_public_key(&input.script_sig, &input.witness) {
+                count.revealed_public_keys += 1;
+                write(BTCAddressType::P2PK, key, &mut count)?;
+            }
+        }
+        for output in &transaction.outputs {
+            count.outputs += 1;
+            if let (address_type, Some(program)) = script::output_program(&output.script_pubkey) {
+                write(address_type, program, &mut count)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// The programs written by a scan, as fixed-size keys per program length: hashes, output keys and
+/// compressed or uncompressed public keys.
+#[derive(Default)]
+struct WrittenPrograms {
+    hashes: HashSet<(BTCAddressType, [u8; 20])>,
+    output_keys: HashSet<(BTCAddressType, [u8; 32])>,
+    compressed_keys: HashSet<[u8; 33]>,
+    uncompressed_keys: HashSet<[u8; 65]>,
+}
+
+impl WrittenPrograms {
+    /// True if the program was not written before.
+    fn insert(&mut self, address_type: BTCAddressType, program: &[u8]) -> bool {
+        if let Ok(hash) = program.try_into() {
+            self.hashes.insert((address_type, hash))
+        } else if let Ok(key) = program.try_into() {
+            self.output_keys.insert((address_type, key))
+        } else if let Ok(key) = program.try_into() {
+            self.compressed_keys.insert(key)
+        } else if let Ok(key) = program.try_into() {
+            self.uncompressed_keys.insert(key)
+        } else {
+            unreachable!(
+                "programs are hashes, output keys or public keys, not {} bytes",
+                program.len()
+            )
+        }
+    }
+}
+
+/// Hands every transaction of the block files to `visit` in order, returns the number of blocks.
+pub fn read_block_files(
+    files: &[PathBuf],
+    network: Network,
+    mut visit: impl FnMut(Transaction) -> Result<(), Error>,
+) -> Result<u64, Error> {
+    let mut blocks = 0;
+    for file in files {
+        let file_name = file.to_string_lossy();
+        let blocks_before = blocks;
+        for transactions in BlockFileReader::new(open_block_file(file)?, &file_name, network) {
+            blocks += 1;
+            for transaction in transactions? {
+                visit(transaction)?;
+            }
+        }
+        info!("Read {}: {} blocks", file_name, blocks - blocks_before);
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address_file::{read_addresses_file, LoadOptions};
+    use crate::block_file::{block_files, scan_block_files, BlockFileReader, ScanCount};
+    use crate::error::Error;
+    use crate::network::Network;
+    use crate::test_util::{temp_dir, temp_file};
+    use hex_literal::hex;
+    use std::path::Path;
+
+    const BLOCKS: &str = "addresses/blocks/blk00000.dat";
+
+    #[test]
+    fn can_read_block_file() {
+        let file = std::fs::File::open(BLOCKS).unwrap();
+        let blocks: Vec<_> = BlockFileReader::new(file, BLOCKS, Network::Regtest)
+            .map(|block| block.unwrap())
+            .collect();
+        assert_eq!(2, blocks.len());
+        assert_eq!(1, blocks[0].len());
+        assert_eq!(2, blocks[1].len());
+
+        let segwit = &blocks[1][1];
+        assert_eq!(2, segwit.inputs.len());
+        assert_eq!(4, segwit.outputs.len());
+        assert_eq!(
+            hex!("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5").to_vec(),
+            segwit.inputs[0].witness[1]
+        );
+        assert_eq!(4000, segwit.outputs[0].value);
+    }
+
+    #[test]
+    fn fails_on_other_network() {
+        let file = std::fs::File::open(BLOCKS).unwrap();
+        let mut blocks = BlockFileReader::new(file, BLOCKS, Network::Mainnet);
+        assert!(matches!(
+            blocks.next(),
+            Some(Err(Error::InvalidData { .. }))
+        ));
+        assert!(blocks.next().is_none());
+    }
+
+    #[test]
+    fn can_scan_block_files() {
+        let output = temp_file("scan.txt");
+        let output = output.to_str().unwrap();
+        let count = scan_block_files(&[BLOCKS.into()], Network::Regtest, output).unwrap();
+        let lines = std::fs::read_to_string(output).unwrap();
+        let targets = read_addresses_file(output, &LoadOptions::default());
+        std::fs::remove_file(output).unwrap();
+
+        assert_eq!(
+            ScanCount {
+                blocks: 2,
+                transactions: 3,
+                outputs: 8,
+                revealed_public_keys: 3,
+                targets: 8,
+            },
+            count
+        );
+        assert_eq!(
+            vec![
+                "p2pkh:751e76e8199196d454941c45d1b3a323f1433bd6",
+                "p2pk:0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                "p2wpkh:06afd46bcdfd22ef94ac122aa11f241244a37ecc",
+                "p2sh:bcfeb728b584253d5f3f70bcb780e9ef218a68f4",
+                "p2pk:02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+                "p2tr:da4710964f7852695de2da025290e24af6d8c281de5a0b902b7135fd9fd74d21",
+                "p2wsh:1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+                "p2pk:04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5\
+                1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a",
+            ],
+            lines.lines().collect::<Vec<_>>()
+        );
+        assert_eq!(8, targets.unwrap().len());
+    }
+
+    #[test]
+    fn can_scan_obfuscated_block_files() {
+        let dir = temp_dir("blocks");
+        let key = hex!("0123456789abcdef");
+        let blocks: Vec<u8> = std::fs::read(BLOCKS)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ key[i % key.len()])
+            .collect();
+        std::fs::write(dir.join("blk00000.dat"), blocks).unwrap();
+        std::fs::write(dir.join("rev00000.dat"), [0u8; 16]).unwrap();
+        std::fs::write(dir.join("xor.dat"), key).unwrap();
+        let output = dir.join("targets.txt.gz");
+
+        let files = block_files(&dir).unwrap();
+        let count = scan_block_files(&files, Network::Regtest, output.to_str().unwrap()).unwrap();
+        let targets = read_addresses_file(output.to_str().unwrap(), &LoadOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(vec![dir.join("blk00000.dat")], files);
+        assert_eq!(8, count.targets);
+        assert_eq!(8, targets.unwrap().len());
+        assert_eq!(
+            vec![Path::new(BLOCKS).to_path_buf()],
+            block_files(Path::new(BLOCKS)).unwrap()
+        );
+    }
+}
@@ -1,72 +1,332 @@
 use crate::btc_address::BTCAddressType::ERR;
-use bech32::segwit;
-use primitive_types::H160;
+use crate::error::Error;
+use crate::network::Network;
+use bech32::{hrp, segwit, Hrp};
+use primitive_types::{H160, H256};
+use secp256k1::PublicKey;
+use sha2::{Digest, Sha256};
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BTCAddressType {
-    P2PK,
+    P2PKH,
     P2SH,
     P2WPKH,
     P2WSH,
+    P2TR,
+    /// Pay-to-pubkey output, given as hex encoded public key since it has no address
+    P2PK,
     MISC,
     ERR,
 }
 
-pub fn get_address_type(address: &str) -> BTCAddressType {
-    if address.starts_with("1") {
+impl BTCAddressType {
+    pub const ALL: [BTCAddressType; 8] = [
+        BTCAddressType::P2PKH,
+        BTCAddressType::P2SH,
+        BTCAddressType::P2WPKH,
+        BTCAddressType::P2WSH,
+        BTCAddressType::P2TR,
+        BTCAddressType::P2PK,
+        BTCAddressType::MISC,
+        BTCAddressType::ERR,
+    ];
+}
+
+/// A set of address types, e.g. the types a target is listed as in the address file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddressTypes(u8);
+
+impl AddressTypes {
+    pub fn of(address_type: BTCAddressType) -> Self {
+        AddressTypes(1 << address_type as u8)
+    }
+
+    pub fn insert(&mut self, address_type: BTCAddressType) {
+        self.0 |= Self::of(address_type).0;
+    }
+
+    pub fn union(self, other: AddressTypes) -> Self {
+        AddressTypes(self.0 | other.0)
+    }
+
+    pub fn contains(&self, address_type: BTCAddressType) -> bool {
+        self.0 & Self::of(address_type).0 != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The first of the types in declaration order, if any.
+    pub fn first(&self) -> Option<BTCAddressType> {
+        BTCAddressType::ALL
+            .into_iter()
+            .find(|address_type| self.contains(*address_type))
+    }
+}
+
+pub fn get_address_type(address: &str, network: Network) -> BTCAddressType {
+    if is_hex_public_key(address) {
         return BTCAddressType::P2PK;
-    } else if address.starts_with("3") {
+    } else if address.starts_with(network.p2pkh_prefixes()) {
+        return BTCAddressType::P2PKH;
+    } else if address.starts_with(network.p2sh_prefixes()) {
         return BTCAddressType::P2SH;
-    } else if address.starts_with("bc1") {
-        let segwit = segwit::decode(address);
-        if segwit.is_err() {
-            return ERR;
-        } else {
-            let (_, _, data) =
-                segwit.unwrap_or_else(|_| panic!("Invalid segwit address: {}", &address));
-            return if data.len() == 20 {
-                BTCAddressType::P2WPKH
-            } else {
-                BTCAddressType::P2WSH
-            };
-        }
+    } else if base58check_payload(address).is_ok() {
+        // a valid address of another network has a version byte of another network
+        return ERR;
+    } else if SEGWIT_HRPS.iter().any(|hrp| has_hrp(address, *hrp)) {
+        return match segwit::decode(address) {
+            Ok((hrp, _, _)) if hrp != network.hrp() => ERR,
+            Ok((_, version, data)) if version == segwit::VERSION_0 => match data.len() {
+                20 => BTCAddressType::P2WPKH,
+                32 => BTCAddressType::P2WSH,
+                _ => ERR,
+            },
+            Ok((_, version, data)) if version == segwit::VERSION_1 && data.len() == 32 => {
+                BTCAddressType::P2TR
+            }
+            // future witness versions and version 1 programs of other lengths
+            Ok(_) => BTCAddressType::MISC,
+            Err(_) => ERR,
+        };
     }
 
     BTCAddressType::MISC
 }
 
-pub fn p2wpkh_address_to_160_bit_hash(address: &str) -> H160 {
-    let (_hrp, _, data) =
-        segwit::decode(address).unwrap_or_else(|_| panic!("Invalid segwit address: {}", &address));
+/// Human readable parts of the segwit addresses of all networks
+const SEGWIT_HRPS: [Hrp; 3] = [hrp::BC, hrp::TB, hrp::BCRT];
+
+/// Whether the address starts with the human readable part, in either case like bech32 allows.
+fn has_hrp(address: &str, hrp: Hrp) -> bool {
+    let prefix = format!("{}1", hrp);
+    address
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(&prefix))
+}
+
+pub fn p2wpkh_address_to_160_bit_hash(address: &str) -> Result<H160, Error> {
+    let data = segwit_program(address, 20)?;
+    Ok(H160::from_slice(&data))
+}
+
+/// The 32-byte output key of a bech32m `bc1p` (`tb1p`, `bcrt1p`) address.
+pub fn p2tr_address_to_output_key(address: &str) -> Result<H256, Error> {
+    let data = segwit_program(address, 32)?;
+    Ok(H256::from_slice(&data))
+}
+
+/// The SHA256 witness script hash of a bech32 version 0 `bc1q` (`tb1q`, `bcrt1q`) address of
+/// 32 bytes.
+pub fn p2wsh_address_to_script_hash(address: &str) -> Result<H256, Error> {
+    match segwit::decode(address) {
+        Ok((_, version, data)) if version == segwit::VERSION_0 && data.len() == 32 => {
+            Ok(H256::from_slice(&data))
+        }
+        _ => Err(Error::InvalidSegwit(address.to_string())),
+    }
+}
+
+fn segwit_program(address: &str, len: usize) -> Result<Vec<u8>, Error> {
+    match segwit::decode(address) {
+        Ok((_, _, data)) if data.len() == len => Ok(data),
+        _ => Err(Error::InvalidSegwit(address.to_string())),
+    }
+}
 
-    let hash: [u8; 20] = data[0..20].try_into().unwrap();
-    H160::from(hash)
+fn is_hex_public_key(address: &str) -> bool {
+    let prefix_ok = match address.len() {
+        66 => address.starts_with("02") || address.starts_with("03"),
+        130 => address.starts_with("04"),
+        _ => false,
+    };
+    prefix_ok && address.bytes().all(|b| b.is_ascii_hexdigit())
 }
 
-pub fn p2pk_address_to_160_bit_hash(address: &str) -> H160 {
-    let decoded = bs58::decode(address).into_vec().unwrap();
+/// The version byte and hash of a Base58Check address of 25 bytes, verifying the checksum.
+fn base58check_payload(address: &str) -> Result<Vec<u8>, Error> {
+    let decoded = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| Error::InvalidBase58(address.to_string()))?;
     if decoded.len() != 25 {
-        panic!("Cannot read p2pk address {}", address);
+        return Err(Error::InvalidLength(address.to_string()));
+    }
+
+    let checksum = Sha256::digest(Sha256::digest(&decoded[0..21]));
+    if checksum[0..4] != decoded[21..25] {
+        return Err(Error::InvalidChecksum(address.to_string()));
+    }
+    Ok(decoded)
+}
+
+/// Decodes a Base58Check address of 25 bytes, verifying checksum and version byte.
+fn base58check_to_160_bit_hash(address: &str, version: u8) -> Result<H160, Error> {
+    let decoded = base58check_payload(address)?;
+    if decoded[0] != version {
+        return Err(Error::WrongVersion {
+            address: address.to_string(),
+            version: decoded[0],
+        });
+    }
+
+    Ok(H160::from_slice(&decoded[1..21]))
+}
+
+/// Why an address of type `ERR` is no target: the version byte of a Base58Check address of another
+/// network, an invalid segwit address otherwise.
+pub fn invalid_address_error(address: &str, network: Network) -> Error {
+    match base58check_to_160_bit_hash(address, network.p2pkh_version()) {
+        Err(error @ Error::WrongVersion { .. }) => error,
+        _ => Error::InvalidSegwit(address.to_string()),
     }
+}
+
+/// The public key hash of a Base58Check P2PKH address of the network.
+pub fn p2pkh_address_to_160_bit_hash(address: &str, network: Network) -> Result<H160, Error> {
+    base58check_to_160_bit_hash(address, network.p2pkh_version())
+}
+
+/// The public key of a P2PK output given as hex, compressed or uncompressed. Returns the point and
+/// whether it was given in compressed form.
+pub fn p2pk_output_to_public_key(output: &str) -> Result<(PublicKey, bool), Error> {
+    let invalid = || Error::InvalidPublicKey(output.to_string());
+    let bytes = decode_hex(output).ok_or_else(invalid)?;
+    let public_key = PublicKey::from_slice(&bytes).map_err(|_| invalid())?;
+    Ok((public_key, bytes.len() == 33))
+}
+
+fn base58check_encode(version: u8, hash: &H160) -> String {
+    let mut data = Vec::with_capacity(25);
+    data.push(version);
+    data.extend_from_slice(hash.as_bytes());
+    let checksum = Sha256::digest(Sha256::digest(&data));
+    data.extend_from_slice(&checksum[0..4]);
+    bs58::encode(data).into_string()
+}
 
-    H160::from_slice(&decoded[1..21])
+/// Base58Check P2PKH address of a public key hash.
+pub fn p2pkh_address(pubkey_hash: &H160, network: Network) -> String {
+    base58check_encode(network.p2pkh_version(), pubkey_hash)
+}
+
+/// Base58Check P2SH address of a script hash.
+pub fn p2sh_address(script_hash: &H160, network: Network) -> String {
+    base58check_encode(network.p2sh_version(), script_hash)
+}
+
+/// Bech32 P2WPKH address of a public key hash.
+pub fn p2wpkh_address(pubkey_hash: &H160, network: Network) -> String {
+    segwit::encode_v0(network.hrp(), pubkey_hash.as_bytes())
+        .expect("20-byte witness program is valid")
+}
+
+/// Bech32 P2WSH address of a witness script hash.
+pub fn p2wsh_address(script_hash: &H256, network: Network) -> String {
+    segwit::encode_v0(network.hrp(), script_hash.as_bytes())
+        .expect("32-byte witness program is valid")
+}
+
+/// Bech32m P2TR address of an output key.
+pub fn p2tr_address(output_key: &H256, network: Network) -> String {
+    segwit::encode_v1(network.hrp(), output_key.as_bytes())
+        .expect("32-byte witness program is valid")
+}
+
+/// A line like `p2wpkh:<hex>` of the raw hash, output key or public key of an address type, the
+/// inverse of `split_type_tag`. Returns `None` for types without a tag.
+pub fn type_tagged(address_type: BTCAddressType, bytes: &[u8]) -> Option<String> {
+    let tag = match address_type {
+        BTCAddressType::P2PKH => "p2pkh",
+        BTCAddressType::P2SH => "p2sh",
+        BTCAddressType::P2WPKH => "p2wpkh",
+        BTCAddressType::P2WSH => "p2wsh",
+        BTCAddressType::P2TR => "p2tr",
+        BTCAddressType::P2PK => "p2pk",
+        BTCAddressType::MISC | BTCAddressType::ERR => return None,
+    };
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Some(format!("{}:{}", tag, hex))
+}
+
+/// Splits a line like `p2wpkh:<hex>` into the address type of its tag and the hex of the raw hash,
+/// or of the public key for `p2pk`. Returns `None` if the line has no known type tag.
+pub fn split_type_tag(line: &str) -> Option<(BTCAddressType, &str)> {
+    let (tag, hex) = line.split_once(':')?;
+    let address_type = match tag {
+        "p2pkh" => BTCAddressType::P2PKH,
+        "p2sh" => BTCAddressType::P2SH,
+        "p2wpkh" => BTCAddressType::P2WPKH,
+        "p2wsh" => BTCAddressType::P2WSH,
+        "p2tr" => BTCAddressType::P2TR,
+        "p2pk" => BTCAddressType::P2PK,
+        _ => return None,
+    };
+    Some((address_type, hex))
+}
+
+/// A hash160 given as 40 hex characters.
+pub fn hex_to_160_bit_hash(hex: &str) -> Result<H160, Error> {
+    match decode_hex(hex) {
+        Some(bytes) if bytes.len() == 20 => Ok(H160::from_slice(&bytes)),
+        _ => Err(Error::InvalidHex {
+            hex: hex.to_string(),
+            bytes: 20,
+        }),
+    }
+}
+
+/// A 32-byte hash or x-only key given as 64 hex characters.
+pub fn hex_to_256_bit_hash(hex: &str) -> Result<H256, Error> {
+    match decode_hex(hex) {
+        Some(bytes) if bytes.len() == 32 => Ok(H256::from_slice(&bytes)),
+        _ => Err(Error::InvalidHex {
+            hex: hex.to_string(),
+            bytes: 32,
+        }),
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// The script hash of a Base58Check P2SH address of the network.
+pub fn p2sh_address_to_160_bit_hash(address: &str, network: Network) -> Result<H160, Error> {
+    base58check_to_160_bit_hash(address, network.p2sh_version())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{hash_util, key_util};
+    use hex_literal::hex;
     use parameterized_macro::parameterized;
     use secp256k1::Secp256k1;
     use std::str::FromStr;
 
     #[test]
     fn can_return_address_type() {
-        assert!(BTCAddressType::P2PK == get_address_type("1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm"));
-        assert!(BTCAddressType::P2SH == get_address_type("3N5i3Vs9UMyjYbBCFNQqU3ybSuDepX7oT3"));
+        assert!(
+            BTCAddressType::P2PKH
+                == get_address_type("1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm", Network::Mainnet)
+        );
+        assert!(
+            BTCAddressType::P2SH
+                == get_address_type("3N5i3Vs9UMyjYbBCFNQqU3ybSuDepX7oT3", Network::Mainnet)
+        );
         assert!(
             BTCAddressType::P2WPKH
-                == get_address_type("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+                == get_address_type(
+                    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                    Network::Mainnet
+                )
         );
     }
 
@@ -79,14 +339,14 @@ mod tests {
     "1cMh228HTCiwS8ZsaakH8A8wze1JR5ZsP", //key is 2
     "1CUNEBjYrCn2y1SdiUMohaKUi4wpP326Lb", //key is 3
     })]
-    fn can_get_hash_from_p2pk(key: u128, uncompressed_address: &str, compressed_address: &str) {
+    fn can_get_hash_from_p2pkh(key: u128, uncompressed_address: &str, compressed_address: &str) {
         assert!(matches!(
-            get_address_type(&uncompressed_address),
-            BTCAddressType::P2PK
+            get_address_type(&uncompressed_address, Network::Mainnet),
+            BTCAddressType::P2PKH
         ));
         assert!(matches!(
-            get_address_type(&compressed_address),
-            BTCAddressType::P2PK
+            get_address_type(&compressed_address, Network::Mainnet),
+            BTCAddressType::P2PKH
         ));
 
         let public_key =
@@ -95,35 +355,310 @@ mod tests {
             hash_util::hash_public_key(&public_key);
         assert_eq!(
             hash_from_compressed_key,
-            p2pk_address_to_160_bit_hash(compressed_address)
+            p2pkh_address_to_160_bit_hash(compressed_address, Network::Mainnet).unwrap()
         );
         assert_eq!(
             hash_from_uncompressed_key,
-            p2pk_address_to_160_bit_hash(uncompressed_address)
+            p2pkh_address_to_160_bit_hash(uncompressed_address, Network::Mainnet).unwrap()
+        );
+    }
+
+    #[parameterized(key = {1, 2, 3}, address = {
+    "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN", //key is 1
+    "3FWHHE3RVgyv5vYmMrcoRdA25uugWvQbso", //key is 2
+    "3BM3eLQZbwubG3XwwxJmd9qxwMJn7yUTSn", //key is 3
+    })]
+    fn can_get_hash_from_p2sh_p2wpkh(key: u128, address: &str) {
+        assert!(matches!(
+            get_address_type(&address, Network::Mainnet),
+            BTCAddressType::P2SH
+        ));
+
+        let public_key =
+            key_util::get_public_key_from_private_key_primitive(key, &Secp256k1::new());
+        let (pubkey_hash, _) = hash_util::hash_public_key(&public_key);
+        assert_eq!(
+            hash_util::p2sh_p2wpkh_script_hash(&pubkey_hash),
+            p2sh_address_to_160_bit_hash(address, Network::Mainnet).unwrap()
         );
     }
 
+    #[parameterized(address = {
+    "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZn", //checksum mismatch
+    "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6k", //too short
+    "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN", //P2SH version byte
+    "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZ0", //not base58
+    })]
+    fn invalid_p2pkh_address(address: &str) {
+        assert!(p2pkh_address_to_160_bit_hash(address, Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn invalid_p2sh_address() {
+        assert!(matches!(
+            p2sh_address_to_160_bit_hash("3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLM", Network::Mainnet),
+            Err(Error::InvalidChecksum(_))
+        ));
+        assert!(matches!(
+            p2sh_address_to_160_bit_hash("1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm", Network::Mainnet),
+            Err(Error::WrongVersion { version: 0, .. })
+        ));
+        assert!(matches!(
+            p2sh_address_to_160_bit_hash("3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZG", Network::Mainnet),
+            Err(Error::InvalidLength(_))
+        ));
+        assert!(matches!(
+            p2sh_address_to_160_bit_hash("3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGL0", Network::Mainnet),
+            Err(Error::InvalidBase58(_))
+        ));
+    }
+
     #[parameterized(address = {
     "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", //bech32
-    "bc1pm9jzmujvdqjj6y28hptk859zs3yyv78hlz84pm", //bech32m
     "bc1qqzzcr0x26mm30v5h0r6j3pe4zkjd0hkpv8qglf", //data is only 19 bytes long
+    "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", //upper case
     }, expected_hash = {
     "751e76e8199196d454941c45d1b3a323f1433bd6",
-    "d9642df24c68252d1147b85763d0a284484678f7",
     "008581bccad6f717b29778f528873515a4d7dec1",
+    "751e76e8199196d454941c45d1b3a323f1433bd6",
     })]
     fn can_get_hash_from_bech32(address: &str, expected_hash: &str) {
-        assert!(matches!(get_address_type(&address), BTCAddressType::P2WPKH));
+        assert!(matches!(
+            get_address_type(&address, Network::Mainnet),
+            BTCAddressType::P2WPKH
+        ));
 
-        let actual = p2wpkh_address_to_160_bit_hash(&address);
+        let actual = p2wpkh_address_to_160_bit_hash(&address).unwrap();
         let expected = H160::from_str(expected_hash).unwrap();
 
         assert_eq!(expected, actual);
     }
 
+    #[parameterized(address = {
+    "bc1pm9jzmujvdqjj6y28hptk859zs3yyv78hlz84pm", //version 1 of 20 bytes
+    "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", //version 2
+    "BC1SW50QGDZ25J", //version 16
+    })]
+    fn future_witness_programs_are_not_targets(address: &str) {
+        assert_eq!(
+            BTCAddressType::MISC,
+            get_address_type(address, Network::Mainnet)
+        );
+    }
+
+    #[parameterized(address = {
+    "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", //BIP350
+    "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5", //BIP341
+    }, expected_key = {
+    "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+    })]
+    fn can_get_output_key_from_p2tr(address: &str, expected_key: &str) {
+        assert!(matches!(
+            get_address_type(&address, Network::Mainnet),
+            BTCAddressType::P2TR
+        ));
+
+        let actual = p2tr_address_to_output_key(&address).unwrap();
+        let expected = H256::from_str(expected_key).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[parameterized(key = {1, 2}, output = {
+    "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798", //compressed, key is 1
+    "04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee51ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a", //uncompressed, key is 2
+    }, compressed = {true, false})]
+    fn can_get_public_key_from_p2pk(key: u128, output: &str, compressed: bool) {
+        assert!(matches!(
+            get_address_type(&output, Network::Mainnet),
+            BTCAddressType::P2PK
+        ));
+
+        let public_key =
+            key_util::get_public_key_from_private_key_primitive(key, &Secp256k1::new());
+        assert_eq!(
+            (public_key, compressed),
+            p2pk_output_to_public_key(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_p2pk_output() {
+        // x-coordinate that is not on the curve
+        let output = "020000000000000000000000000000000000000000000000000000000000000005";
+        assert!(matches!(
+            get_address_type(output, Network::Mainnet),
+            BTCAddressType::P2PK
+        ));
+        assert!(matches!(
+            p2pk_output_to_public_key(output),
+            Err(Error::InvalidPublicKey(_))
+        ));
+        assert!(matches!(
+            get_address_type(
+                "0579be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                Network::Mainnet
+            ),
+            BTCAddressType::MISC
+        ));
+    }
+
+    #[parameterized(network = {Network::Testnet, Network::Signet, Network::Regtest}, p2wpkh_address = {
+    "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+    "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+    "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+    }, p2tr_address = {
+    "tb1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5ssk79hv2",
+    "tb1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5ssk79hv2",
+    "bcrt1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5ssm803es",
+    })]
+    fn can_read_test_network_addresses(network: Network, p2wpkh_address: &str, p2tr_address: &str) {
+        // all addresses belong to key 1
+        let p2pkh_address = "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r";
+        let p2sh_address = "2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN";
+        let pubkey_hash = H160::from_str("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+
+        assert!(BTCAddressType::P2PKH == get_address_type(p2pkh_address, network));
+        assert_eq!(
+            pubkey_hash,
+            p2pkh_address_to_160_bit_hash(p2pkh_address, network).unwrap()
+        );
+        assert!(BTCAddressType::P2SH == get_address_type(p2sh_address, network));
+        assert_eq!(
+            hash_util::p2sh_p2wpkh_script_hash(&pubkey_hash),
+            p2sh_address_to_160_bit_hash(p2sh_address, network).unwrap()
+        );
+        assert!(BTCAddressType::P2WPKH == get_address_type(p2wpkh_address, network));
+        assert_eq!(
+            pubkey_hash,
+            p2wpkh_address_to_160_bit_hash(p2wpkh_address).unwrap()
+        );
+        assert!(BTCAddressType::P2TR == get_address_type(p2tr_address, network));
+        assert_eq!(
+            H256::from_str("da4710964f7852695de2da025290e24af6d8c281de5a0b902b7135fd9fd74d21")
+                .unwrap(),
+            p2tr_address_to_output_key(p2tr_address).unwrap()
+        );
+
+        // mainnet addresses are invalid on test networks
+        assert!(ERR == get_address_type("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", network));
+        assert!(ERR == get_address_type("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", network));
+    }
+
+    #[test]
+    fn test_network_addresses_on_mainnet() {
+        assert!(ERR == get_address_type("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r", Network::Mainnet));
+        assert!(
+            ERR == get_address_type(
+                "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+                Network::Mainnet
+            )
+        );
+    }
+
+    #[test]
+    fn can_split_type_tag() {
+        let hash = "751e76e8199196d454941c45d1b3a323f1433bd6";
+        let (address_type, hex) =
+            split_type_tag("p2wpkh:751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert!(BTCAddressType::P2WPKH == address_type);
+        assert_eq!(hash, hex);
+        assert_eq!(
+            H160::from_str(hash).unwrap(),
+            hex_to_160_bit_hash(hex).unwrap()
+        );
+
+        assert!(split_type_tag("1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm").is_none());
+        assert!(split_type_tag("p2xyz:751e76e8199196d454941c45d1b3a323f1433bd6").is_none());
+    }
+
+    #[test]
+    fn can_tag_type() {
+        let line = type_tagged(
+            BTCAddressType::P2WPKH,
+            &hex!("751e76e8199196d454941c45d1b3a323f1433bd6"),
+        )
+        .unwrap();
+        assert_eq!("p2wpkh:751e76e8199196d454941c45d1b3a323f1433bd6", line);
+        assert!(split_type_tag(&line).is_some_and(|(t, _)| t == BTCAddressType::P2WPKH));
+        assert!(type_tagged(BTCAddressType::MISC, &[0x6a]).is_none());
+    }
+
+    #[parameterized(hex = {
+    "751e76e8199196d454941c45d1b3a323f1433b", //19 bytes
+    "751e76e8199196d454941c45d1b3a323f1433bd6aa", //21 bytes
+    "751e76e8199196d454941c45d1b3a323f1433bdx", //not hex
+    "751e76e8199196d454941c45d1b3a323f1433bd", //odd length
+    })]
+    fn invalid_hex_hash(hex: &str) {
+        assert!(matches!(
+            hex_to_160_bit_hash(hex),
+            Err(Error::InvalidHex { bytes: 20, .. })
+        ));
+    }
+
+    #[parameterized(network = {Network::Mainnet, Network::Testnet, Network::Regtest}, p2pkh = {
+    "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+    "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r",
+    "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r",
+    }, p2sh = {
+    "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN",
+    "2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN",
+    "2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN",
+    }, p2wpkh = {
+    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+    "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+    "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+    }, p2tr = {
+    "bc1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5sspknck9",
+    "tb1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5ssk79hv2",
+    "bcrt1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5ssm803es",
+    })]
+    fn can_encode_addresses(network: Network, p2pkh: &str, p2sh: &str, p2wpkh: &str, p2tr: &str) {
+        // all addresses belong to key 1
+        let pubkey_hash = H160::from_str("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let output_key =
+            H256::from_str("da4710964f7852695de2da025290e24af6d8c281de5a0b902b7135fd9fd74d21")
+                .unwrap();
+
+        assert_eq!(p2pkh, p2pkh_address(&pubkey_hash, network));
+        assert_eq!(
+            p2sh,
+            p2sh_address(&hash_util::p2sh_p2wpkh_script_hash(&pubkey_hash), network)
+        );
+        assert_eq!(p2wpkh, p2wpkh_address(&pubkey_hash, network));
+        assert_eq!(p2tr, p2tr_address(&output_key, network));
+    }
+
+    #[test]
+    fn can_encode_bip350_p2tr_address() {
+        let output_key =
+            H256::from_str("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .unwrap();
+        assert_eq!(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            p2tr_address(&output_key, Network::Mainnet)
+        );
+    }
+
+    #[test]
+    fn can_collect_address_types() {
+        let mut address_types = AddressTypes::of(BTCAddressType::P2PKH);
+        address_types.insert(BTCAddressType::P2WPKH);
+        assert!(address_types.contains(BTCAddressType::P2PKH));
+        assert!(address_types.contains(BTCAddressType::P2WPKH));
+        assert!(!address_types.contains(BTCAddressType::P2SH));
+        assert_eq!(
+            address_types,
+            AddressTypes::of(BTCAddressType::P2WPKH).union(AddressTypes::of(BTCAddressType::P2PKH))
+        );
+        assert!(AddressTypes::default().is_empty());
+    }
+
     #[test]
     fn wrong_bech_encoding() {
         let x = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd";
-        assert!(matches!(get_address_type(x), ERR));
+        assert!(matches!(get_address_type(x, Network::Mainnet), ERR));
     }
 }
@@ -1,24 +1,31 @@
+use crate::btc_address::AddressTypes;
 use crate::collider::PointStrategy::{
     OriginalPoint, OriginalPointLambda, OriginalPointLambdaNegated, OriginalPointLambdaSquared,
     OriginalPointLambdaSquaredNegated, OriginalPointNegated,
 };
+use crate::network::Network;
+use crate::script_template::ScriptTemplate;
 use crate::search_space::SearchSpace;
+use crate::targets::{Target, Targets};
 use crate::wif::private_key_to_wif;
-use crate::{hash_util, key_util};
+use crate::{btc_address, hash_util, key_util};
 use hex_literal::hex;
 use log::info;
 use num_bigint::BigUint;
 use num_traits::{Euclid, One, ToPrimitive};
-use primitive_types::H160;
-use secp256k1::{All, PublicKey, Scalar, Secp256k1};
+use primitive_types::{H160, H256};
+use secp256k1::{All, Parity, PublicKey, Scalar, Secp256k1};
 use std::cell::LazyCell;
-use std::collections::HashSet;
 use std::ops::{Add, Mul, Sub};
 use std::time::SystemTime;
 
 pub struct Collider<'a> {
-    pub addresses: &'a HashSet<H160>,
+    pub targets: &'a Targets,
     pub secp: &'a Secp256k1<All>,
+    pub network: Network,
+    /// Scripts over the candidate public key whose commitments are matched against script hash
+    /// targets
+    pub script_templates: &'a [ScriptTemplate],
 }
 
 #[derive(Clone)]
@@ -31,6 +38,66 @@ pub struct FoundKey {
     pub key: BigUint,
     pub strategy: PointStrategy,
     pub compressed: bool,
+    pub encoding: Encoding,
+    /// Balance of the matched target in satoshis, if the address file has one
+    pub balance: Option<u64>,
+    /// The public key that matched, of the point the strategy derived from the key
+    pub public_key: PublicKey,
+    pub target: Target,
+    /// Types the matched target is listed as in the address file
+    pub address_types: AddressTypes,
+    /// Labels of the address files that list the matched target
+    pub labels: Vec<String>,
+}
+
+impl FoundKey {
+    /// The matched addresses with their type, followed by the compressed and uncompressed P2PKH
+    /// address of the public key.
+    pub fn address_info(&self, network: Network) -> String {
+        let matched = self
+            .target
+            .addresses(self.address_types, network)
+            .into_iter()
+            .map(|(address_type, address)| format!("{:?} {}", address_type, address))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (compressed, uncompressed) = hash_util::hash_public_key(&self.public_key);
+        format!(
+            "Address {}. Derived P2PKH compressed {}, uncompressed {}",
+            matched,
+            btc_address::p2pkh_address(&compressed, network),
+            btc_address::p2pkh_address(&uncompressed, network)
+        )
+    }
+
+    /// `, balance <n> sat` if the balance of the matched target is known, empty otherwise.
+    pub fn balance_info(&self) -> String {
+        self.balance
+            .map(|balance| format!(", balance {} sat", balance))
+            .unwrap_or_default()
+    }
+
+    /// `, labels [<label>, ...]` if the matched target is labelled, empty otherwise.
+    pub fn label_info(&self) -> String {
+        if self.labels.is_empty() {
+            String::new()
+        } else {
+            format!(", labels [{}]", self.labels.join(", "))
+        }
+    }
+}
+
+/// How the public key of a found key is encoded in the matched target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    /// HASH160 of the public key (P2PKH or P2WPKH)
+    PubkeyHash,
+    /// P2SH or P2WSH commitment to a script over the public key, e.g. P2SH-P2WPKH
+    Script(ScriptTemplate),
+    /// BIP341 key-path output key of the public key as internal key (P2TR)
+    Taproot,
+    /// The public key itself (P2PK)
+    PublicKey,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -159,39 +226,179 @@ impl Collider<'_> {
         point_strategy: &PointStrategy,
         found_keys: &mut Vec<FoundKey>,
     ) {
-        let (compressed, uncompressed) = hash_util::hash_public_key(&public_key);
+        if self.targets.compressed_public_keys.contains(public_key) {
+            self.found(
+                current_key,
+                point_strategy,
+                public_key,
+                true,
+                Encoding::PublicKey,
+                Target::PublicKey(*public_key, true),
+                found_keys,
+            );
+        }
+        if self.targets.uncompressed_public_keys.contains(public_key) {
+            self.found(
+                current_key,
+                point_strategy,
+                public_key,
+                false,
+                Encoding::PublicKey,
+                Target::PublicKey(*public_key, false),
+                found_keys,
+            );
+        }
 
-        if self.addresses.contains(&compressed) {
-            let found_key = FoundKey {
-                key: current_key.clone(),
-                strategy: point_strategy.clone(),
-                compressed: true,
-            };
-            Self::log_collision(&found_key);
-            found_keys.push(found_key);
+        // public keys are compared as they are, hashing is only needed for hash targets
+        let search_hashes = !self.targets.pubkey_hashes.is_empty();
+        let search_scripts = !self.script_templates.is_empty()
+            && (!self.targets.script_hashes.is_empty()
+                || !self.targets.witness_script_hashes.is_empty());
+        if search_hashes || search_scripts {
+            let hashes = hash_util::hash_public_key(public_key);
+            if search_hashes {
+                self.search_hashes(current_key, public_key, &hashes, point_strategy, found_keys);
+            }
+            if search_scripts {
+                self.search_scripts(current_key, public_key, &hashes, point_strategy, found_keys);
+            }
         }
-        if self.addresses.contains(&uncompressed) {
-            let found_key = FoundKey {
-                key: current_key.clone(),
-                strategy: point_strategy.clone(),
-                compressed: false,
+        if !self.targets.output_keys.is_empty() {
+            // P and -P share the internal key, only the point with even y is checked to report it once
+            let (internal_key, parity) = public_key.x_only_public_key();
+            let output_key = match parity {
+                Parity::Even => key_util::taproot_output_key(&internal_key, self.secp),
+                Parity::Odd => None,
             };
-            Self::log_collision(&found_key);
-            found_keys.push(found_key);
+            if let Some(output_key) = output_key {
+                let output_key = H256::from(output_key.serialize());
+                if self.targets.output_keys.contains(&output_key) {
+                    self.found(
+                        current_key,
+                        point_strategy,
+                        public_key,
+                        true,
+                        Encoding::Taproot,
+                        Target::OutputKey(output_key),
+                        found_keys,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Matches the compressed and uncompressed public key hashes against the pubkey hash targets.
+    fn search_hashes(
+        &self,
+        current_key: &BigUint,
+        public_key: &PublicKey,
+        (compressed, uncompressed): &(H160, H160),
+        point_strategy: &PointStrategy,
+        found_keys: &mut Vec<FoundKey>,
+    ) {
+        if self.targets.pubkey_hashes.contains(compressed) {
+            self.found(
+                current_key,
+                point_strategy,
+                public_key,
+                true,
+                Encoding::PubkeyHash,
+                Target::PubkeyHash(*compressed),
+                found_keys,
+            );
+        }
+        if self.targets.pubkey_hashes.contains(uncompressed) {
+            self.found(
+                current_key,
+                point_strategy,
+                public_key,
+                false,
+                Encoding::PubkeyHash,
+                Target::PubkeyHash(*uncompressed),
+                found_keys,
+            );
+        }
+    }
+
+    /// Matches the commitments of the script templates over both serializations of the public key
+    /// against the script hash targets.
+    fn search_scripts(
+        &self,
+        current_key: &BigUint,
+        public_key: &PublicKey,
+        (compressed_hash, uncompressed_hash): &(H160, H160),
+        point_strategy: &PointStrategy,
+        found_keys: &mut Vec<FoundKey>,
+    ) {
+        let compressed = public_key.serialize();
+        let uncompressed = public_key.serialize_uncompressed();
+        let keys = [
+            (&compressed[..], compressed_hash, true),
+            (&uncompressed[..], uncompressed_hash, false),
+        ];
+        for template in self.script_templates {
+            for (serialized, pubkey_hash, is_compressed) in keys {
+                let Some(target) = template.commitment(serialized, pubkey_hash) else {
+                    continue;
+                };
+                if self.targets.contains(&target) {
+                    self.found(
+                        current_key,
+                        point_strategy,
+                        public_key,
+                        is_compressed,
+                        Encoding::Script(*template),
+                        target,
+                        found_keys,
+                    );
+                }
+            }
         }
     }
 
-    fn log_collision(found_key: &FoundKey) {
+    #[allow(clippy::too_many_arguments)]
+    fn found(
+        &self,
+        current_key: &BigUint,
+        point_strategy: &PointStrategy,
+        public_key: &PublicKey,
+        compressed: bool,
+        encoding: Encoding,
+        target: Target,
+        found_keys: &mut Vec<FoundKey>,
+    ) {
+        // the info is only looked up for a match, the sets alone are searched
+        let info = self.targets.get(&target).unwrap_or_default();
+        let found_key = FoundKey {
+            key: current_key.clone(),
+            strategy: point_strategy.clone(),
+            compressed,
+            encoding,
+            balance: info.balance,
+            public_key: *public_key,
+            target,
+            address_types: info.address_types,
+            labels: self.targets.label_names(info.labels),
+        };
+        self.log_collision(&found_key);
+        found_keys.push(found_key);
+    }
+
+    fn log_collision(&self, found_key: &FoundKey) {
         info!(
-            "Collision found for {:?}, {}. Key {}. WIF {}",
+            "Collision found for {:?}, {:?}, {}{}{}. Key {}. WIF {}. {}",
             found_key.strategy,
+            found_key.encoding,
             if found_key.compressed {
                 "compressed"
             } else {
                 "uncompressed"
             },
+            found_key.balance_info(),
+            found_key.label_info(),
             found_key.key.to_str_radix(16),
-            private_key_to_wif(&found_key.key, found_key.compressed)
+            private_key_to_wif(&found_key.key, found_key.compressed, self.network),
+            found_key.address_info(self.network)
         )
     }
 
@@ -210,8 +417,10 @@ impl Collider<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::address_file::read_addresses_file;
+    use crate::address_file::{read_addresses_file, LoadOptions};
     use crate::btc_address;
+    use crate::btc_address::BTCAddressType;
+    use crate::targets::TargetInfo;
     use crate::wif::wif_to_private_key;
     use num_traits::{Num, ToPrimitive};
     use parameterized_macro::parameterized;
@@ -220,7 +429,8 @@ mod tests {
 
     #[test]
     fn puzzle_transactions() {
-        let addresses = read_addresses_file("addresses/puzzle_3_to_7.txt.gz");
+        let addresses =
+            read_addresses_file("addresses/puzzle_3_to_7.txt.gz", &LoadOptions::default()).unwrap();
         assert_eq!(5, addresses.len());
 
         let start_inclusive: u32 = 1;
@@ -229,8 +439,10 @@ mod tests {
         let end_exclusive = BigUint::from(end_exclusive);
 
         let collider = Collider {
-            addresses: &addresses,
+            targets: &addresses,
             secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
         };
 
         let result = collider.run(SearchSpace {
@@ -250,15 +462,18 @@ mod tests {
 
     #[test]
     fn puzzle_transaction_69() {
-        let addresses = read_addresses_file("addresses/puzzle_69.txt.gz");
+        let addresses =
+            read_addresses_file("addresses/puzzle_69.txt.gz", &LoadOptions::default()).unwrap();
         assert_eq!(1, addresses.len());
 
         let start_inclusive: BigUint = BigUint::from_str_radix("101d83275fb2bc7e00", 16).unwrap();
         let end_exclusive = start_inclusive.clone().add(BigUint::from(1024usize));
 
         let collider = Collider {
-            addresses: &addresses,
+            targets: &addresses,
             secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
         };
 
         let result = collider.run(SearchSpace {
@@ -280,7 +495,7 @@ mod tests {
     })]
     fn can_find_keys(wif: &str) {
         let file = format!("addresses/wif/{}.txt.gz", wif);
-        let addresses = read_addresses_file(&file);
+        let addresses = read_addresses_file(&file, &LoadOptions::default()).unwrap();
         assert_eq!(2, addresses.len());
 
         let private_key = wif_to_private_key(wif);
@@ -301,13 +516,15 @@ mod tests {
         ));
     }
 
-    fn run_collider_test(private_key: BigUint, addresses: &HashSet<H160>) -> ColliderResult {
+    fn run_collider_test(private_key: BigUint, targets: &Targets) -> ColliderResult {
         let start_inclusive = private_key.clone();
         let end_exclusive = private_key.add(BigUint::one());
 
         let collider = Collider {
-            addresses,
+            targets,
             secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
         };
 
         collider.run(SearchSpace {
@@ -326,8 +543,10 @@ mod tests {
     "bc1qa3kkplcah8jrn7uj0xjrvydhpmx3cxqw3uzx45",
     })]
     fn segwit(hex_key: &str, address: &str) {
-        let mut addresses = HashSet::new();
-        addresses.insert(btc_address::p2wpkh_address_to_160_bit_hash(&address));
+        let mut addresses = Targets::new();
+        addresses
+            .pubkey_hashes
+            .insert(btc_address::p2wpkh_address_to_160_bit_hash(&address).unwrap());
         let key = BigUint::from_str_radix(&hex_key, 16).unwrap();
 
         let result = run_collider_test(key.clone(), &addresses);
@@ -352,8 +571,10 @@ mod tests {
     "bc1qqqq49mw5zp95ps4d0kr4cqaa7cuv47r6adhk43"
     })]
     fn segwit_from_wif(wif: &str, address: &str) {
-        let mut addresses = HashSet::new();
-        addresses.insert(btc_address::p2wpkh_address_to_160_bit_hash(&address));
+        let mut addresses = Targets::new();
+        addresses
+            .pubkey_hashes
+            .insert(btc_address::p2wpkh_address_to_160_bit_hash(&address).unwrap());
         let key = BigUint::from_bytes_be(&wif_to_private_key(wif));
 
         let result = run_collider_test(key.clone(), &addresses);
@@ -386,12 +607,12 @@ mod tests {
             "0376cdf3e4f29b709454a95ba0fc4242edf5f5685be94b6b09d36bf91280da5de5",
         ];
 
-        let mut addresses = HashSet::new();
+        let mut addresses = Targets::new();
         for public_key in PUBLIC_KEYS {
             let public_key = PublicKey::from_str(public_key).unwrap();
             let (compressed, uncompressed) = hash_util::hash_public_key(&public_key);
-            addresses.insert(compressed);
-            addresses.insert(uncompressed);
+            addresses.pubkey_hashes.insert(compressed);
+            addresses.pubkey_hashes.insert(uncompressed);
         }
 
         let private_key = BigUint::from_str_radix(&private_key, 16).unwrap();
@@ -419,4 +640,210 @@ mod tests {
 
         assert_eq!(12, result.found_keys.len());
     }
+
+    #[test]
+    fn nested_segwit() {
+        let addresses =
+            read_addresses_file("addresses/nested_segwit.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(3, addresses.len());
+
+        let collider = Collider {
+            targets: &addresses,
+            secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
+        };
+
+        let result = collider.run(SearchSpace {
+            start_inclusive: BigUint::one(),
+            end_exclusive: BigUint::from(10u32),
+        });
+
+        assert_eq!(3, result.found_keys.len());
+        for (i, found_key) in result.found_keys.iter().enumerate() {
+            assert_eq!(i as u32 + 1, found_key.key.to_u32().unwrap());
+            assert_eq!(OriginalPoint, found_key.strategy);
+            assert_eq!(
+                Encoding::Script(ScriptTemplate::DEFAULT[0]),
+                found_key.encoding
+            );
+            assert!(found_key.compressed);
+        }
+    }
+
+    #[test]
+    fn script_templates() {
+        let mut addresses = Targets::new();
+        // wsh(pk) of key 1 compressed
+        let witness_script_hash = btc_address::hex_to_256_bit_hash(
+            "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+        )
+        .unwrap();
+        addresses.witness_script_hashes.insert(witness_script_hash);
+        // sh(pk) of key 1 uncompressed
+        let script_hash =
+            btc_address::hex_to_160_bit_hash("31edc23bdafda4639e669f89ad6b2318dd79d032").unwrap();
+        addresses.script_hashes.insert(script_hash);
+        let search_space = SearchSpace {
+            start_inclusive: BigUint::one(),
+            end_exclusive: BigUint::from(10u32),
+        };
+
+        let collider = Collider {
+            targets: &addresses,
+            secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
+        };
+        assert!(collider.run(search_space.clone()).found_keys.is_empty());
+
+        let templates = ["wsh(pk)".parse().unwrap(), "sh(pk)".parse().unwrap()];
+        let collider = Collider {
+            script_templates: &templates,
+            ..collider
+        };
+        let result = collider.run(search_space);
+
+        assert_eq!(2, result.found_keys.len());
+        let wsh = &result.found_keys[0];
+        assert_eq!(1, wsh.key.to_u32().unwrap());
+        assert_eq!(Encoding::Script(templates[0]), wsh.encoding);
+        assert!(wsh.compressed);
+        assert!(wsh.address_info(Network::Mainnet).starts_with(
+            "Address P2WSH bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3. "
+        ));
+        let sh = &result.found_keys[1];
+        assert_eq!(Encoding::Script(templates[1]), sh.encoding);
+        assert!(!sh.compressed);
+    }
+
+    #[test]
+    fn reports_balance() {
+        let mut addresses = Targets::new();
+        let public_key = key_util::get_public_key_from_private_key_primitive(1, &Secp256k1::new());
+        let (compressed, _) = hash_util::hash_public_key(&public_key);
+        let labels = addresses.add_label("puzzle").unwrap();
+        let labels = labels.union(addresses.add_label("regtest-drill").unwrap());
+        addresses.insert(
+            Target::PubkeyHash(compressed),
+            TargetInfo {
+                balance: Some(5000),
+                address_types: AddressTypes::of(BTCAddressType::P2WPKH),
+                labels,
+                type_balances: None,
+            },
+        );
+
+        let result = run_collider_test(BigUint::one(), &addresses);
+
+        assert_eq!(1, result.found_keys.len());
+        assert_eq!(Some(5000), result.found_keys[0].balance);
+        assert_eq!(", balance 5000 sat", result.found_keys[0].balance_info());
+        assert_eq!(
+            ", labels [puzzle, regtest-drill]",
+            result.found_keys[0].label_info()
+        );
+        assert_eq!(
+            "Address P2WPKH bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4. \
+            Derived P2PKH compressed 1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH, \
+            uncompressed 1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+            result.found_keys[0].address_info(Network::Mainnet)
+        );
+    }
+
+    #[parameterized(file = {
+    "addresses/nested_segwit.txt.gz",
+    "addresses/taproot.txt.gz",
+    "addresses/p2pk.txt.gz",
+    "addresses/puzzle_3_to_7.txt.gz",
+    }, address = {
+    "P2SH 3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN",
+    "P2TR bc1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5sspknck9",
+    "P2PK 0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    "P2PKH 19ZewH8Kk1PDbSNdJ97FP4EiCjTRaZMZQA",
+    }, key = {
+    "1", "1", "1", "7",
+    })]
+    fn reports_matched_address(file: &str, address: &str, key: &str) {
+        let addresses = read_addresses_file(file, &LoadOptions::default()).unwrap();
+        let key = BigUint::from_str_radix(key, 16).unwrap();
+
+        let result = run_collider_test(key, &addresses);
+
+        assert!(!result.found_keys.is_empty());
+        let address_info = result.found_keys[0].address_info(Network::Mainnet);
+        assert!(
+            address_info.starts_with(&format!("Address {}. ", address)),
+            "{}",
+            address_info
+        );
+    }
+
+    #[test]
+    fn taproot() {
+        let addresses =
+            read_addresses_file("addresses/taproot.txt.gz", &LoadOptions::default()).unwrap();
+        assert_eq!(4, addresses.len());
+
+        let collider = Collider {
+            targets: &addresses,
+            secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
+        };
+
+        let result = collider.run(SearchSpace {
+            start_inclusive: BigUint::one(),
+            end_exclusive: BigUint::from(10u32),
+        });
+
+        assert_eq!(3, result.found_keys.len());
+        for (i, found_key) in result.found_keys.iter().enumerate() {
+            assert_eq!(i as u32 + 1, found_key.key.to_u32().unwrap());
+            assert_eq!(Encoding::Taproot, found_key.encoding);
+        }
+
+        let key = BigUint::from_str_radix(
+            "6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa",
+            16,
+        )
+        .unwrap();
+        let result = run_collider_test(key.clone(), &addresses);
+        assert_eq!(1, result.found_keys.len());
+        assert_eq!(key, result.found_keys.get(0).unwrap().key);
+        assert_eq!(
+            Encoding::Taproot,
+            result.found_keys.get(0).unwrap().encoding
+        );
+    }
+
+    #[test]
+    fn p2pk() {
+        let addresses =
+            read_addresses_file("addresses/p2pk.txt.gz", &LoadOptions::default()).unwrap();
+
+        let collider = Collider {
+            targets: &addresses,
+            secp: &Secp256k1::new(),
+            network: Network::Mainnet,
+            script_templates: ScriptTemplate::DEFAULT,
+        };
+
+        let result = collider.run(SearchSpace {
+            start_inclusive: BigUint::one(),
+            end_exclusive: BigUint::from(10u32),
+        });
+
+        assert_eq!(2, result.found_keys.len());
+        let first = result.found_keys.get(0).unwrap();
+        assert_eq!(1, first.key.to_u32().unwrap());
+        assert!(first.compressed);
+        assert_eq!(OriginalPoint, first.strategy);
+        assert_eq!(Encoding::PublicKey, first.encoding);
+        let second = result.found_keys.get(1).unwrap();
+        assert_eq!(2, second.key.to_u32().unwrap());
+        assert!(!second.compressed);
+        assert_eq!(OriginalPoint, second.strategy);
+        assert_eq!(Encoding::PublicKey, second.encoding);
+    }
 }
@@ -0,0 +1,436 @@
+use crate::address_file;
+use crate::address_file::{AddressSource, LoadOptions};
+use crate::btc_address::AddressTypes;
+use crate::error::Error;
+use crate::network::Network;
+use crate::targets::{Target, TargetInfo, Targets};
+use log::info;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// How often a `DeltaWatcher` is polled for new delta files
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Targets a delta adds, updates and removes.
+#[derive(Debug, Default, PartialEq)]
+pub struct DeltaCount {
+    pub added: u64,
+    /// Targets already listed whose balance or address types change
+    pub updated: u64,
+    pub removed: u64,
+}
+
+/// The changes of a delta file: lines `+<target>[<TAB><balance>]` add a target or replace its
+/// balance and address types, lines `-<target>` remove it. Targets are addresses or type tagged
+/// hex like in address files. With a minimum balance, a target added below it is removed instead.
+#[derive(Debug, Default)]
+pub struct Delta {
+    additions: Targets,
+    removals: Vec<Target>,
+}
+
+impl Delta {
+    /// Reads a delta file, plain or compressed like an address file.
+    pub fn read(file: &str, options: &LoadOptions) -> Result<Self, Error> {
+        let reader = BufReader::new(address_file::open_address_file(file)?);
+        let mut delta = Delta::default();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| Error::io(file, e))?;
+            delta
+                .parse_line(line.trim(), options.network)
+                .map_err(|e| e.at_line(file, index + 1))?;
+        }
+        // the balance of a target is on its first line only, the minimum applies once all are read
+        let below_min_balance: Vec<Target> = delta
+            .additions
+            .iter()
+            .filter(|(_, info)| !options.has_min_balance(info.balance))
+            .map(|(target, _)| target)
+            .collect();
+        for target in below_min_balance {
+            delta.additions.remove(&target);
+            delta.removals.push(target);
+        }
+        Ok(delta)
+    }
+
+    fn parse_line(&mut self, line: &str, network: Network) -> Result<(), Error> {
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        if let Some(address) = line.strip_prefix('-') {
+            if let (_, Some(target)) = address_file::parse_target(address.trim(), network)? {
+                self.removals.push(target);
+            }
+        } else if let Some(rest) = line.strip_prefix('+') {
+            let (address, balance) = match rest.split_once('\t') {
+                Some((address, balance)) => (
+                    address,
+                    Some(
+                        balance
+                            .trim()
+                            .parse()
+                            .map_err(|_| Error::InvalidBalance(balance.to_string()))?,
+                    ),
+                ),
+                None => (rest, None),
+            };
+            if let (address_type, Some(target)) =
+                address_file::parse_target(address.trim(), network)?
+            {
+                let info = TargetInfo {
+                    balance,
+                    address_types: AddressTypes::of(address_type),
+                    ..Default::default()
+                };
+                self.additions.insert(target, info);
+            }
+        } else {
+            return Err(Error::InvalidDeltaLine(line.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Applies the removals and then the additions to the targets. Added targets get the label,
+    /// updated targets keep their labels as well.
+    pub fn apply(&self, targets: &mut Targets, label: &str) -> Result<DeltaCount, Error> {
+        let labels = targets.add_label(label)?;
+        let mut count = DeltaCount::default();
+        for target in &self.removals {
+            if targets.remove(target).is_some() {
+                count.removed += 1;
+            }
+        }
+        for (target, info) in self.additions.iter() {
+            let mut info = TargetInfo {
+                labels,
+                ..info.clone()
+            };
+            match targets.remove(&target) {
+                Some(old) => {
+                    count.updated += 1;
+                    info.labels = old.labels.union(labels);
+                }
+                None => count.added += 1,
+            }
+            targets.insert(target, info);
+        }
+        Ok(count)
+    }
+}
+
+/// Applies the delta files of the sources in order, a directory of deltas in name order. The
+/// targets a delta adds are labelled with the label of its source.
+pub fn apply_deltas(
+    targets: &mut Targets,
+    sources: &[AddressSource],
+    options: &LoadOptions,
+) -> Result<DeltaCount, Error> {
+    let mut total = DeltaCount::default();
+    for source in sources {
+        for (_, file) in source.files()? {
+            let count = Delta::read(&file, options)?.apply(targets, &source.label())?;
+            log_applied(&file, &count);
+            total.added += count.added;
+            total.updated += count.updated;
+            total.removed += count.removed;
+        }
+    }
+    Ok(total)
+}
+
+fn log_applied(file: &str, count: &DeltaCount) {
+    info!(
+        "Applied delta {}: {} added, {} updated, {} removed",
+        file, count.added, count.updated, count.removed
+    );
+}
+
+/// Writes the delta that turns the old targets into the new ones, removals first, each part
+/// sorted. A target whose balance or address types differ is listed as addition.
+pub fn write_delta(old: &Targets, new: &Targets, output: &str) -> Result<DeltaCount, Error> {
+    let mut count = DeltaCount::default();
+    let mut removals: Vec<String> = old
+        .iter()
+        .filter(|(target, _)| new.get(target).is_none())
+        .map(|(target, info)| target.tagged(info.address_types).swap_remove(0))
+        .collect();
+    removals.sort();
+    count.removed = removals.len() as u64;
+
+    let mut additions: Vec<Vec<String>> = Vec::new();
+    for (target, info) in new.iter() {
+        match old.get(&target) {
+            Some(old_info)
+                if old_info.balance == info.balance
+                    && old_info.address_types == info.address_types =>
+            {
+                continue
+            }
+            Some(_) => count.updated += 1,
+            None => count.added += 1,
+        }
+        let mut lines = target.tagged(info.address_types);
+        if let Some(balance) = info.balance {
+            lines[0] = format!("{}\t{}", lines[0], balance);
+        }
+        additions.push(lines);
+    }
+    additions.sort();
+
+    address_file::create_address_file(output, |writer| {
+        for line in removals {
+            writeln!(writer, "-{}", line).map_err(|e| Error::io(output, e))?;
+        }
+        for line in additions.iter().flatten() {
+            writeln!(writer, "+{}", line).map_err(|e| Error::io(output, e))?;
+        }
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// Applies the delta files that appear in a directory to targets in use. Write a delta under a
+/// hidden name and rename it when it is complete, hidden files are not applied.
+pub struct DeltaWatcher {
+    source: AddressSource,
+    options: LoadOptions,
+    applied: HashSet<String>,
+}
+
+impl DeltaWatcher {
+    pub fn new(source: AddressSource, options: LoadOptions) -> Self {
+        DeltaWatcher {
+            source,
+            options,
+            applied: HashSet::new(),
+        }
+    }
+
+    /// Applies the delta files not applied by an earlier poll in name order, each under the write
+    /// lock of the targets. A delta that fails is not tried again.
+    pub fn poll(&mut self, targets: &RwLock<Targets>) -> Result<Vec<(String, DeltaCount)>, Error> {
+        let mut applied = Vec::new();
+        for (_, file) in self.source.files()? {
+            if !self.applied.insert(file.clone()) {
+                continue;
+            }
+            let delta = Delta::read(&file, &self.options)?;
+            let count = delta.apply(&mut targets.write().unwrap(), &self.source.label())?;
+            log_applied(&file, &count);
+            applied.push((file, count));
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address_file::{read_addresses_file, AddressSource, Columns, LoadOptions};
+    use crate::btc_address;
+    use crate::delta::{apply_deltas, write_delta, Delta, DeltaCount, DeltaWatcher};
+    use crate::error::Error;
+    use crate::targets::{Target, TargetInfo, Targets};
+    use crate::test_util::temp_dir;
+    use std::fs;
+    use std::sync::RwLock;
+
+    const KEY_1_HASH: &str = "751e76e8199196d454941c45d1b3a323f1433bd6";
+    const KEY_2_HASH: &str = "06afd46bcdfd22ef94ac122aa11f241244a37ecc";
+    const SCRIPT_HASH: &str = "bcfeb728b584253d5f3f70bcb780e9ef218a68f4";
+
+    fn pubkey_hash(hex: &str) -> Target {
+        Target::PubkeyHash(btc_address::hex_to_160_bit_hash(hex).unwrap())
+    }
+
+    fn balances() -> LoadOptions {
+        LoadOptions {
+            columns: Columns {
+                balance: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_delta_between_lists() {
+        let dir = temp_dir("delta-write");
+        let old = dir.join("old.txt");
+        let new = dir.join("new.txt");
+        let delta = dir.join("new.delta");
+        fs::write(
+            &old,
+            format!("p2pkh:{}\t100\np2sh:{}\t200\n", KEY_1_HASH, SCRIPT_HASH),
+        )
+        .unwrap();
+        fs::write(
+            &new,
+            format!(
+                "p2pkh:{}\t150\np2wpkh:{}\t0\np2pkh:{}\t300\n",
+                KEY_1_HASH, KEY_1_HASH, KEY_2_HASH
+            ),
+        )
+        .unwrap();
+
+        let old = read_addresses_file(old.to_str().unwrap(), &balances()).unwrap();
+        let new = read_addresses_file(new.to_str().unwrap(), &balances()).unwrap();
+        let count = write_delta(&old, &new, delta.to_str().unwrap()).unwrap();
+        let lines = fs::read_to_string(&delta).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            DeltaCount {
+                added: 1,
+                updated: 1,
+                removed: 1,
+            },
+            count
+        );
+        assert_eq!(
+            vec![
+                format!("-p2sh:{}", SCRIPT_HASH),
+                format!("+p2pkh:{}\t300", KEY_2_HASH),
+                format!("+p2pkh:{}\t150", KEY_1_HASH),
+                format!("+p2wpkh:{}", KEY_1_HASH),
+            ],
+            lines.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn applies_chain_of_deltas() {
+        let dir = temp_dir("delta-chain");
+        fs::write(
+            dir.join("2024-01-02.delta"),
+            format!(
+                "# day 2\n+p2pkh:{}\t300\n-p2sh:{}\n",
+                KEY_2_HASH, SCRIPT_HASH
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("2024-01-03.delta"),
+            format!("+p2wpkh:{}\t50\n-p2pkh:{}\n", KEY_1_HASH, KEY_2_HASH),
+        )
+        .unwrap();
+        let base = dir.join("base.txt");
+        fs::write(
+            &base,
+            format!("p2pkh:{}\t100\np2sh:{}\t200\n", KEY_1_HASH, SCRIPT_HASH),
+        )
+        .unwrap();
+
+        let mut targets = read_addresses_file(base.to_str().unwrap(), &balances()).unwrap();
+        fs::remove_file(base).unwrap();
+        let deltas = AddressSource::new(dir.to_str().unwrap());
+        let count = apply_deltas(&mut targets, &[deltas], &balances()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            DeltaCount {
+                added: 1,
+                updated: 1,
+                removed: 2,
+            },
+            count
+        );
+        assert_eq!(1, targets.len());
+        let info = targets.get(&pubkey_hash(KEY_1_HASH)).unwrap();
+        assert_eq!(Some(50), info.balance);
+        assert_eq!(
+            vec![
+                "base".to_string(),
+                dir.file_name().unwrap().to_string_lossy().to_string()
+            ],
+            targets.label_names(info.labels)
+        );
+    }
+
+    #[test]
+    fn removes_additions_below_min_balance() {
+        let dir = temp_dir("delta-min-balance");
+        let file = dir.join("1.delta");
+        fs::write(
+            &file,
+            format!(
+                "+p2pkh:{}\t150\n+p2wpkh:{}\n+p2pkh:{}\t50\n+p2sh:{}\n",
+                KEY_1_HASH, KEY_1_HASH, KEY_2_HASH, SCRIPT_HASH
+            ),
+        )
+        .unwrap();
+        let mut targets = Targets::new();
+        for hash in [KEY_1_HASH, KEY_2_HASH] {
+            targets.insert(pubkey_hash(hash), TargetInfo::default());
+        }
+        let options = LoadOptions {
+            min_balance: Some(100),
+            ..Default::default()
+        };
+        let delta = Delta::read(file.to_str().unwrap(), &options);
+        fs::remove_dir_all(&dir).unwrap();
+        let count = delta.unwrap().apply(&mut targets, "delta").unwrap();
+
+        // the balance of key 1 is on its first line, key 2 drops below the minimum
+        assert_eq!(
+            DeltaCount {
+                added: 0,
+                updated: 1,
+                removed: 1,
+            },
+            count
+        );
+        assert_eq!(1, targets.len());
+        let info = targets.get(&pubkey_hash(KEY_1_HASH)).unwrap();
+        assert_eq!(Some(150), info.balance);
+    }
+
+    #[test]
+    fn fails_on_line_without_sign() {
+        let dir = temp_dir("delta-invalid");
+        let file = dir.join("invalid.delta");
+        fs::write(
+            &file,
+            format!("+p2pkh:{}\np2pkh:{}\n", KEY_1_HASH, KEY_2_HASH),
+        )
+        .unwrap();
+        let result = Delta::read(file.to_str().unwrap(), &LoadOptions::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        match result {
+            Err(Error::InvalidLine { line, source, .. }) => {
+                assert_eq!(2, line);
+                assert!(matches!(*source, Error::InvalidDeltaLine(_)));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn watcher_applies_new_deltas_once() {
+        let dir = temp_dir("delta-watch");
+        let targets = RwLock::new(
+            read_addresses_file("addresses/test.txt", &LoadOptions::default()).unwrap(),
+        );
+        let mut watcher = DeltaWatcher::new(
+            AddressSource::new(dir.to_str().unwrap()),
+            LoadOptions::default(),
+        );
+        assert!(watcher.poll(&targets).unwrap().is_empty());
+
+        fs::write(dir.join(".1.delta"), format!("+p2pkh:{}\n", KEY_1_HASH)).unwrap();
+        assert!(watcher.poll(&targets).unwrap().is_empty());
+
+        fs::rename(dir.join(".1.delta"), dir.join("1.delta")).unwrap();
+        let applied = watcher.poll(&targets).unwrap();
+        let polled_again = watcher.poll(&targets).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(1, applied.len());
+        assert_eq!(1, applied[0].1.added);
+        assert!(polled_again.is_empty());
+        assert_eq!(2, targets.read().unwrap().len());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::address_file;
+use crate::btc_address;
+use crate::btc_address::BTCAddressType;
+use crate::error::Error;
+use crate::network::Network;
+use crate::script_template::ScriptTemplate;
+use crate::targets::Target;
+use crate::{hash_util, key_util};
+use primitive_types::H256;
+use secp256k1::{All, Secp256k1, XOnlyPublicKey};
+use std::sync::LazyLock;
+
+/// Characters of a descriptor in the order of the checksum algorithm of Bitcoin Core
+const INPUT_CHARSET: &[u8] =
+    b"0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u64; 5] = [
+    0xf5dee51989,
+    0xa9fdca3312,
+    0x1bab10e32d,
+    0x3706b1677a,
+    0x644d626ffd,
+];
+
+static SECP: LazyLock<Secp256k1<All>> = LazyLock::new(Secp256k1::new);
+
+/// Whether a line of an address file is an output descriptor rather than an address or hex.
+pub fn is_descriptor(line: &str) -> bool {
+    line.contains('(')
+}
+
+/// The target of an output descriptor with a literal public key: `pk(KEY)`, `pkh(KEY)`,
+/// `wpkh(KEY)`, `tr(KEY)` without script tree, `addr(ADDRESS)`, or `sh(...)`, `wsh(...)` and
+/// `sh(wsh(...))` of a single key script like the script templates. Keys may have an origin like
+/// `[d34db33f/84h/0h/0h]`, a checksum after `#` has to match.
+pub fn parse_descriptor(
+    descriptor: &str,
+    network: Network,
+) -> Result<(BTCAddressType, Target), Error> {
+    let invalid = |reason: &str| Error::InvalidDescriptor {
+        descriptor: descriptor.to_string(),
+        reason: reason.to_string(),
+    };
+    let body = match descriptor.split_once('#') {
+        Some((body, expected)) => {
+            if checksum(body).as_deref() != Some(expected) {
+                return Err(invalid("checksum does not match"));
+            }
+            body
+        }
+        None => descriptor,
+    };
+
+    let mut functions = Vec::new();
+    let mut argument = body;
+    while let Some((function, inner)) = argument.split_once('(') {
+        functions.push(function);
+        argument = inner
+            .strip_suffix(')')
+            .ok_or_else(|| invalid("parentheses do not match"))?;
+    }
+    if argument.contains(')') {
+        return Err(invalid("parentheses do not match"));
+    }
+
+    if functions == ["addr"] {
+        return match address_file::parse_target(argument, network)? {
+            (address_type, Some(target))
+                if address_type != BTCAddressType::P2PK
+                    && btc_address::split_type_tag(argument).is_none() =>
+            {
+                Ok((address_type, target))
+            }
+            _ => Err(invalid("not an address with a target")),
+        };
+    }
+    if functions == ["tr"] {
+        let internal_key = x_only_key(argument).ok_or_else(|| invalid("not a literal key"))?;
+        let output_key = key_util::taproot_output_key(&internal_key, &SECP)
+            .ok_or_else(|| invalid("key has no taproot output key"))?;
+        return Ok((
+            BTCAddressType::P2TR,
+            Target::OutputKey(H256::from(output_key.serialize())),
+        ));
+    }
+
+    let (public_key, compressed) = btc_address::p2pk_output_to_public_key(without_origin(argument))
+        .map_err(|_| invalid("not a literal public key"))?;
+    let key = if compressed {
+        public_key.serialize().to_vec()
+    } else {
+        public_key.serialize_uncompressed().to_vec()
+    };
+    let pubkey_hash = hash_util::hash160(&key);
+    match functions.as_slice() {
+        ["pk"] => Ok((
+            BTCAddressType::P2PK,
+            Target::PublicKey(public_key, compressed),
+        )),
+        ["pkh"] => Ok((BTCAddressType::P2PKH, Target::PubkeyHash(pubkey_hash))),
+        ["wpkh"] if compressed => Ok((BTCAddressType::P2WPKH, Target::PubkeyHash(pubkey_hash))),
+        ["wpkh"] => Err(invalid("segwit scripts need a compressed key")),
+        [wrapper @ ("sh" | "wsh"), ..] => {
+            let template: ScriptTemplate =
+                format!("{}{}", functions.join("("), ")".repeat(functions.len() - 1))
+                    .parse()
+                    .map_err(|_| invalid("not a single key script"))?;
+            let target = template
+                .commitment(&key, &pubkey_hash)
+                .ok_or_else(|| invalid("segwit scripts need a compressed key"))?;
+            let address_type = if *wrapper == "sh" {
+                BTCAddressType::P2SH
+            } else {
+                BTCAddressType::P2WSH
+            };
+            Ok((address_type, target))
+        }
+        _ => Err(invalid("not a single key script")),
+    }
+}
+
+/// The x-only key of a `tr` key expression, given as x-only or compressed key.
+fn x_only_key(key: &str) -> Option<XOnlyPublicKey> {
+    let key = without_origin(key);
+    match btc_address::hex_to_256_bit_hash(key) {
+        Ok(x) => XOnlyPublicKey::from_byte_array(x.0).ok(),
+        Err(_) => match btc_address::p2pk_output_to_public_key(key) {
+            Ok((public_key, true)) => Some(public_key.x_only_public_key().0),
+            _ => None,
+        },
+    }
+}
+
+fn without_origin(key: &str) -> &str {
+    match key.strip_prefix('[').and_then(|key| key.split_once(']')) {
+        Some((_origin, key)) => key,
+        None => key,
+    }
+}
+
+/// The 8 character checksum Bitcoin Core appends to a descriptor after `#`.
+pub fn checksum(descriptor: &str) -> Option<String> {
+    let mut symbols = Vec::with_capacity(descriptor.len() * 4 / 3 + 9);
+    let mut groups = Vec::with_capacity(3);
+    for c in descriptor.bytes() {
+        let value = INPUT_CHARSET.iter().position(|&v| v == c)? as u64;
+        symbols.push(value & 31);
+        groups.push(value >> 5);
+        if groups.len() == 3 {
+            symbols.push(groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups.as_slice() {
+        [a] => symbols.push(*a),
+        [a, b] => symbols.push(a * 3 + b),
+        _ => {}
+    }
+    symbols.extend([0; 8]);
+
+    let checksum = polymod(&symbols) ^ 1;
+    Some(
+        (0..8)
+            .map(|i| CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+fn polymod(symbols: &[u64]) -> u64 {
+    let mut checksum: u64 = 1;
+    for value in symbols {
+        let top = checksum >> 35;
+        checksum = ((checksum & 0x7ffffffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_address::{AddressTypes, BTCAddressType};
+    use crate::descriptor::{checksum, parse_descriptor};
+    use crate::error::Error;
+    use crate::network::Network;
+    use parameterized_macro::parameterized;
+
+    #[parameterized(descriptor = {
+    "pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)#8fhd9pwu",
+    "pkh([d34db33f/44h/0h/0h]0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)#jfjjhh62",
+    "wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)#ucxz0gak",
+    "sh(wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798))#jqtwwlah",
+    "wsh(pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798))#fdpqq2al",
+    "tr(79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)#gxjkeue2",
+    "tr(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+    "pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+    "addr(mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r)",
+    }, expected = {
+    "p2pkh:06afd46bcdfd22ef94ac122aa11f241244a37ecc",
+    "p2pkh:751e76e8199196d454941c45d1b3a323f1433bd6",
+    "p2wpkh:751e76e8199196d454941c45d1b3a323f1433bd6",
+    "p2sh:bcfeb728b584253d5f3f70bcb780e9ef218a68f4",
+    "p2wsh:1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+    "p2tr:da4710964f7852695de2da025290e24af6d8c281de5a0b902b7135fd9fd74d21",
+    "p2tr:da4710964f7852695de2da025290e24af6d8c281de5a0b902b7135fd9fd74d21",
+    "p2pk:0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    "p2pkh:751e76e8199196d454941c45d1b3a323f1433bd6",
+    })]
+    fn can_parse_descriptor(descriptor: &str, expected: &str) {
+        let (address_type, target) = parse_descriptor(descriptor, Network::Regtest).unwrap();
+        assert_eq!(
+            vec![expected],
+            target.tagged(AddressTypes::of(address_type))
+        );
+    }
+
+    #[parameterized(descriptor = {
+    "wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)#ucxz0gaq",
+    "wpkh(xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0/*)",
+    "wpkh(0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8)",
+    "multi(1,0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+    "sh(wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+    "addr(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+    })]
+    fn rejects_unsupported_descriptor(descriptor: &str) {
+        let result = parse_descriptor(descriptor, Network::Regtest);
+        assert!(
+            matches!(result, Err(Error::InvalidDescriptor { .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn computes_checksum() {
+        assert_eq!(
+            Some("02wpgw69".to_string()),
+            checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)")
+        );
+        assert_eq!(None, checksum("pkh(é)"));
+    }
+
+    #[test]
+    fn addr_is_parsed_for_the_network() {
+        let result = parse_descriptor("addr(mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r)", Network::Mainnet);
+        assert!(result.is_err());
+        assert_eq!(
+            BTCAddressType::P2SH,
+            parse_descriptor(
+                "addr(2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN)",
+                Network::Regtest
+            )
+            .unwrap()
+            .0
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use crate::btc_address::BTCAddressType;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Errors of parsing addresses and loading address files.
+#[derive(Debug)]
+pub enum Error {
+    /// A file cannot be opened, read or written
+    Io { file: String, source: io::Error },
+    /// Not a base58 string
+    InvalidBase58(String),
+    /// Decodes to a different number of bytes than a Base58Check address has
+    InvalidLength(String),
+    /// The Base58Check checksum does not match
+    InvalidChecksum(String),
+    /// The version byte does not belong to the expected address type on the selected network
+    WrongVersion { address: String, version: u8 },
+    /// Not a valid bech32 or bech32m segwit address
+    InvalidSegwit(String),
+    /// Not a hex string of the expected number of bytes
+    InvalidHex { hex: String, bytes: usize },
+    /// Not a public key on the curve
+    InvalidPublicKey(String),
+    /// A line of an address table has fewer columns than configured
+    MissingColumn(usize),
+    /// The balance column does not hold a number of satoshis
+    InvalidBalance(String),
+    /// More address files with distinct labels than a target set can tell apart
+    TooManyLabels(String),
+    /// Not an output descriptor of a single literal key or an address
+    InvalidDescriptor { descriptor: String, reason: String },
+    /// A line of a delta file does not start with + or -
+    InvalidDeltaLine(String),
+    /// A valid line of an address type the collider does not match
+    UnmatchedTarget(BTCAddressType),
+    /// A UTXO snapshot or block file is not in the expected format
+    InvalidData { file: String, reason: String },
+    /// A line of an address file cannot be read as target
+    InvalidLine {
+        file: String,
+        line: usize,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    pub fn io(file: &str, source: io::Error) -> Self {
+        Error::Io {
+            file: file.to_string(),
+            source,
+        }
+    }
+
+    pub fn invalid_data(file: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidData {
+            file: file.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn at_line(self, file: &str, line: usize) -> Self {
+        Error::InvalidLine {
+            file: file.to_string(),
+            line,
+            source: Box::new(self),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { file, source } => write!(f, "{}: {}", file, source),
+            Error::InvalidBase58(address) => write!(f, "'{}' is not base58 encoded", address),
+            Error::InvalidLength(address) => {
+                write!(f, "'{}' does not decode to 25 bytes", address)
+            }
+            Error::InvalidChecksum(address) => {
+                write!(f, "checksum of '{}' does not match", address)
+            }
+            Error::WrongVersion { address, version } => write!(
+                f,
+                "version byte 0x{:02x} of '{}' is not expected on this network",
+                version, address
+            ),
+            Error::InvalidSegwit(address) => write!(f, "'{}' is not a segwit address", address),
+            Error::InvalidHex { hex, bytes } => {
+                write!(f, "'{}' is not a hex string of {} bytes", hex, bytes)
+            }
+            Error::InvalidPublicKey(output) => write!(f, "'{}' is not a valid public key", output),
+            Error::MissingColumn(column) => write!(f, "column {} is missing", column),
+            Error::InvalidBalance(balance) => {
+                write!(f, "'{}' is not a balance in satoshis", balance)
+            }
+            Error::TooManyLabels(label) => write!(
+                f,
+                "cannot add label '{}', at most {} labels are supported",
+                label,
+                crate::targets::Labels::MAX
+            ),
+            Error::InvalidDescriptor { descriptor, reason } => {
+                write!(
+                    f,
+                    "'{}' is not a supported descriptor: {}",
+                    descriptor, reason
+                )
+            }
+            Error::UnmatchedTarget(address_type) => {
+                write!(
+                    f,
+                    "{:?} is not an address type the collider matches",
+                    address_type
+                )
+            }
+            Error::InvalidDeltaLine(line) => write!(
+                f,
+                "'{}' is not a delta line, expected +<target> or -<target>",
+                line
+            ),
+            Error::InvalidData { file, reason } => write!(f, "{}: {}", file, reason),
+            Error::InvalidLine { file, line, source } => {
+                write!(f, "{} line {}: {}", file, line, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::InvalidLine { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
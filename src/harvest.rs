@@ -0,0 +1,210 @@
+use crate::address_file;
+use crate::block_file;
+use crate::btc_address;
+use crate::btc_address::BTCAddressType;
+use crate::error::Error;
+use crate::hash_util;
+use crate::network::Network;
+use crate::script;
+use crate::targets::{Target, Targets};
+use primitive_types::H160;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// What harvesting public keys found.
+#[derive(Debug, Default, PartialEq)]
+pub struct HarvestCount {
+    pub blocks: u64,
+    /// Public keys revealed by inputs or P2PK outputs, with repetitions
+    pub revealed_public_keys: u64,
+    /// Pubkey hash and script hash targets whose public key was found
+    pub paired_targets: u64,
+    /// Pubkey hash and script hash targets that still have to be matched by hash
+    pub unpaired_targets: u64,
+}
+
+/// Writes the public key of every pubkey hash target, and of every P2SH-P2WPKH script hash target,
+/// that the block files reveal to an address file, tagged with each address type of the target like
+/// `p2pkh:<public key>`. If the targets have balances, every line has the balance of its address type
+/// as second column, 0 if it is unknown. Loading that file instead of the hashes lets the collider compare the
+/// points directly, without hashing every candidate, and still report the funded address.
+pub fn harvest_public_keys(
+    targets: &Targets,
+    files: &[PathBuf],
+    network: Network,
+    output: &str,
+) -> Result<HarvestCount, Error> {
+    let mut count = HarvestCount::default();
+    let mut paired: HashSet<H160> = HashSet::new();
+    // every line has the balance column or none does, so the file loads with its balances
+    let balances = targets.has_balances();
+
+    address_file::create_address_file(output, |writer| {
+        let mut pair = |key: &[u8], count: &mut HarvestCount| {
+            count.revealed_public_keys += 1;
+            let hash = hash_util::hash160(key);
+            let mut revealed = Vec::new();
+            if targets.pubkey_hashes.contains(&hash) && paired.insert(hash) {
+                revealed.push(Target::PubkeyHash(hash));
+            }
+            if key.len() == 33 {
+                let script_hash = hash_util::p2sh_p2wpkh_script_hash(&hash);
+                if targets.script_hashes.contains(&script_hash) && paired.insert(script_hash) {
+                    revealed.push(Target::ScriptHash(script_hash));
+                }
+            }
+            for target in revealed {
+                let info = targets.get(&target).unwrap_or_default();
+                for (address_type, _) in target.addresses(info.address_types, network) {
+                    let line = btc_address::type_tagged(address_type, key)
+                        .expect("hash address types have a tag");
+                    let line = match balances {
+                        true => format!("{}\t{}", line, info.balance_of(address_type).unwrap_or(0)),
+                        false => line,
+                    };
+                    writeln!(writer, "{}", line).map_err(|e| Error::io(output, e))?;
+                }
+            }
+            Ok(())
+        };
+
+        count.blocks = block_file::read_block_files(files, network, |transaction| {
+            for input in &transaction.inputs {
+                if let Some(key) = script::revealed_public_key(&input.script_sig, &input.witness) {
+                    pair(key, &mut count)?;
+                }
+            }
+            for output in &transaction.outputs {
+                if let (BTCAddressType::P2PK, Some(key)) =
+                    script::output_program(&output.script_pubkey)
+                {
+                    pair(key, &mut count)?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(())
+    })?;
+
+    count.paired_targets = paired.len() as u64;
+    count.unpaired_targets =
+        (targets.pubkey_hashes.len() + targets.script_hashes.len()) as u64 - count.paired_targets;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address_file::{read_addresses_file, Columns, LoadOptions, Strictness};
+    use crate::btc_address;
+    use crate::btc_address::{AddressTypes, BTCAddressType};
+    use crate::harvest::{harvest_public_keys, HarvestCount};
+    use crate::network::Network;
+    use crate::targets::{Target, TargetInfo, Targets};
+    use crate::test_util::temp_file;
+
+    #[test]
+    fn pairs_hash_targets_with_revealed_public_keys() {
+        let mut targets = Targets::new();
+        // key 1 compressed, revealed by a P2PKH spend, funded as P2PKH and P2WPKH address
+        let hash =
+            btc_address::hex_to_160_bit_hash("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let info = TargetInfo {
+            balance: Some(5000),
+            address_types: AddressTypes::of(BTCAddressType::P2PKH)
+                .union(AddressTypes::of(BTCAddressType::P2WPKH)),
+            ..Default::default()
+        };
+        targets.insert(Target::PubkeyHash(hash), info);
+        for hash in [
+            // key 2 uncompressed, revealed by a P2PK output
+            "d6c8e828c1eca1bba065e1b83e1dc2a36e387a42",
+            // never revealed
+            "0000000000000000000000000000000000000001",
+        ] {
+            let hash = btc_address::hex_to_160_bit_hash(hash).unwrap();
+            targets.pubkey_hashes.insert(hash);
+        }
+        // P2SH-P2WPKH of key 1, paired by the same public key
+        let script_hash =
+            btc_address::hex_to_160_bit_hash("bcfeb728b584253d5f3f70bcb780e9ef218a68f4").unwrap();
+        targets.script_hashes.insert(script_hash);
+
+        let output = temp_file("harvest.txt");
+        let output = output.to_str().unwrap();
+        let count = harvest_public_keys(
+            &targets,
+            &["addresses/blocks/blk00000.dat".into()],
+            Network::Regtest,
+            output,
+        )
+        .unwrap();
+        let lines = std::fs::read_to_string(output).unwrap();
+        let public_keys = read_addresses_file(output, &LoadOptions::default());
+        let balance_options = LoadOptions {
+            strictness: Strictness::Fail,
+            columns: Columns {
+                balance: Some(1),
+                ..Default::default()
+            },
+            skip_header: false,
+            ..Default::default()
+        };
+        let funded_public_keys = read_addresses_file(output, &balance_options);
+        std::fs::remove_file(output).unwrap();
+
+        assert_eq!(
+            HarvestCount {
+                blocks: 2,
+                revealed_public_keys: 4,
+                paired_targets: 3,
+                unpaired_targets: 1,
+            },
+            count
+        );
+        assert_eq!(
+            vec![
+                "p2pkh:0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\t5000",
+                "p2wpkh:0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\t0",
+                "p2sh:0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\t0",
+                "p2pkh:04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5\
+                1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a\t0",
+            ],
+            lines.lines().collect::<Vec<_>>()
+        );
+        let public_keys = public_keys.unwrap();
+        assert_eq!(1, public_keys.compressed_public_keys.len());
+        assert_eq!(1, public_keys.uncompressed_public_keys.len());
+        // a hit on the public key is reported as the addresses it was harvested for
+        let (target, info) = public_keys
+            .iter()
+            .find(|(target, _)| matches!(target, Target::PublicKey(_, true)))
+            .unwrap();
+        assert_eq!(
+            vec![
+                (
+                    BTCAddressType::P2PKH,
+                    "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r".to_string()
+                ),
+                (
+                    BTCAddressType::P2WPKH,
+                    "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string()
+                ),
+                (
+                    BTCAddressType::P2SH,
+                    "2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN".to_string()
+                ),
+            ],
+            target.addresses(info.address_types, Network::Regtest)
+        );
+        // every line has the balance column, the balances of the addresses of a key add up
+        let funded_public_keys = funded_public_keys.unwrap();
+        let balance = |compressed| {
+            funded_public_keys
+                .iter()
+                .find(|(target, _)| matches!(target, Target::PublicKey(_, c) if *c == compressed))
+                .and_then(|(_, info)| info.balance)
+        };
+        assert_eq!(Some(5000), balance(true));
+        assert_eq!(Some(0), balance(false));
+    }
+}
@@ -1,4 +1,4 @@
-use primitive_types::H160;
+use primitive_types::{H160, H256};
 use ripemd::Ripemd160;
 use secp256k1::PublicKey;
 use sha2::{Digest, Sha256};
@@ -11,3 +11,29 @@ pub fn hash_public_key(public_key: &PublicKey) -> (H160, H160) {
         H160::from_slice(&uncompressed.as_slice()),
     )
 }
+
+pub fn hash160(data: &[u8]) -> H160 {
+    H160::from_slice(Ripemd160::digest(Sha256::digest(data)).as_slice())
+}
+
+pub fn sha256(data: &[u8]) -> H256 {
+    H256::from_slice(Sha256::digest(data).as_slice())
+}
+
+/// HASH160 of the P2WPKH program `0x0014 || pubkey_hash` a P2SH-P2WPKH output commits to.
+pub fn p2sh_p2wpkh_script_hash(pubkey_hash: &H160) -> H160 {
+    let mut redeem_script = [0u8; 22];
+    redeem_script[1] = 0x14;
+    redeem_script[2..].copy_from_slice(pubkey_hash.as_bytes());
+    hash160(&redeem_script)
+}
+
+/// BIP340 tagged hash `SHA256(SHA256(tag) || SHA256(tag) || data)`.
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(data);
+    hasher.finalize().into()
+}
@@ -0,0 +1,164 @@
+use crate::address_file;
+use crate::address_file::{AddressSource, LoadOptions, LoadReport};
+use crate::error::Error;
+use crate::targets::{LayoutEstimate, StoreStats};
+use std::fmt::{Display, Formatter};
+
+/// What address files hold and what loading them costs, without starting a search.
+#[derive(Debug)]
+pub struct Inspection {
+    pub labels: Vec<String>,
+    pub report: LoadReport,
+    /// Number of distinct targets
+    pub targets: usize,
+    /// Public key hashes listed both as P2PKH and as P2WPKH address
+    pub pubkey_hash_overlap: usize,
+    pub stores: Vec<StoreStats>,
+    pub layouts: Vec<LayoutEstimate>,
+}
+
+pub fn inspect(sources: &[AddressSource], options: &LoadOptions) -> Result<Inspection, Error> {
+    let (targets, report) = address_file::read_address_sources_with_report(sources, options)?;
+    Ok(Inspection {
+        labels: targets.labels.clone(),
+        report,
+        targets: targets.len(),
+        pubkey_hash_overlap: targets.pubkey_hash_overlap(),
+        stores: targets.store_stats(),
+        layouts: targets.layout_estimates(),
+    })
+}
+
+impl Display for Inspection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let count = &self.report.address_count;
+        writeln!(f, "Labels: {}", self.labels.join(", "))?;
+        writeln!(f, "Lines: {}", count.lines())?;
+        writeln!(f, "  P2PKH: {}", count.p2pkh)?;
+        writeln!(f, "  P2SH: {}", count.p2sh)?;
+        writeln!(f, "  P2WPKH: {}", count.p2wpkh)?;
+        writeln!(f, "  P2WSH: {}", count.p2wsh)?;
+        writeln!(f, "  P2TR: {}", count.p2tr)?;
+        writeln!(f, "  P2PK: {}", count.p2pk)?;
+        writeln!(f, "  Other: {}", count.misc)?;
+        writeln!(f, "  Invalid: {}", count.err)?;
+        writeln!(f, "  Below minimum balance: {}", count.below_min_balance)?;
+        writeln!(f, "Targets: {}", self.targets)?;
+        writeln!(f, "Duplicates: {}", self.report.duplicates)?;
+        writeln!(f, "P2PKH/P2WPKH overlap: {}", self.pubkey_hash_overlap)?;
+
+        writeln!(f, "Memory:")?;
+        for store in &self.stores {
+            writeln!(
+                f,
+                "  {}: {} entries, capacity {}, {}",
+                store.name,
+                store.len,
+                store.capacity,
+                format_bytes(store.bytes)
+            )?;
+        }
+        let total = self.stores.iter().map(|store| store.bytes).sum();
+        writeln!(f, "  total: {}", format_bytes(total))?;
+        writeln!(f, "Estimated memory by layout:")?;
+        for layout in &self.layouts {
+            writeln!(f, "  {}: {}", layout.name, format_bytes(layout.bytes))?;
+        }
+
+        if !self.report.invalid_lines.is_empty() {
+            writeln!(
+                f,
+                "Invalid lines (first {} of {}):",
+                self.report.invalid_lines.len(),
+                count.err
+            )?;
+            for invalid in &self.report.invalid_lines {
+                writeln!(
+                    f,
+                    "  {}:{}\t{}\t{}",
+                    invalid.file, invalid.line_number, invalid.line, invalid.error
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address_file::{AddressSource, LoadOptions};
+    use crate::inspect::{format_bytes, inspect};
+    use crate::test_util::temp_file;
+    use parameterized_macro::parameterized;
+
+    #[test]
+    fn can_inspect_file() {
+        let file = temp_file("inspect.txt");
+        std::fs::write(
+            &file,
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\n\
+            bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\n\
+            1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\n\
+            1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMX\n\
+            3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN\n",
+        )
+        .unwrap();
+        let source = AddressSource::new(file.to_str().unwrap());
+        let inspection = inspect(&[source], &LoadOptions::default());
+        std::fs::remove_file(&file).unwrap();
+        let inspection = inspection.unwrap();
+
+        let count = &inspection.report.address_count;
+        assert_eq!(5, count.lines());
+        assert_eq!(2, count.p2pkh);
+        assert_eq!(1, count.p2wpkh);
+        assert_eq!(1, count.p2sh);
+        assert_eq!(1, count.err);
+        assert_eq!(2, inspection.targets);
+        assert_eq!(1, inspection.report.duplicates);
+        assert_eq!(1, inspection.pubkey_hash_overlap);
+
+        assert_eq!(1, inspection.report.invalid_lines.len());
+        assert_eq!(4, inspection.report.invalid_lines[0].line_number);
+        let store = &inspection.stores[0];
+        assert_eq!(("pubkey hashes", 1), (store.name, store.len));
+        assert!(store.bytes >= store.capacity * 20);
+
+        let layout = |name: &str| {
+            let layout = inspection.layouts.iter().find(|l| l.name == name);
+            layout.unwrap().bytes
+        };
+        // a pubkey hash listed as P2PKH and P2WPKH, which needs an info entry, and a script hash
+        assert_eq!(2 * 20, layout("sorted arrays"));
+        assert!(layout("sorted arrays with sorted info table") > layout("sorted arrays"));
+        assert!(layout("hash sets") > layout("sorted arrays"));
+        assert!(layout("hash sets with info table") > layout("hash sets"));
+        assert!(layout("hash maps with info per entry") > layout("hash sets"));
+
+        let output = inspection.to_string();
+        assert!(output.contains("P2PKH/P2WPKH overlap: 1\n"));
+        assert!(output.contains("  sorted arrays: 40 bytes\n"));
+        assert!(output.contains(":4\t1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMX\t"));
+    }
+
+    #[parameterized(bytes = {0, 1023, 1024, 1536 * 1024, 3 * 1024 * 1024 * 1024}, expected = {
+    "0 bytes", "1023 bytes", "1.0 KiB", "1.5 MiB", "3.0 GiB"
+    })]
+    fn can_format_bytes(bytes: usize, expected: &str) {
+        assert_eq!(expected, format_bytes(bytes));
+    }
+}
@@ -1,4 +1,5 @@
-use secp256k1::{All, PublicKey, Secp256k1, SecretKey};
+use crate::hash_util;
+use secp256k1::{All, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey};
 
 pub fn get_public_key_from_private_key_primitive(key: u128, secp: &Secp256k1<All>) -> PublicKey {
     let mut k: [u8; 32] = [0; 32];
@@ -24,9 +25,24 @@ pub fn get_public_key_from_secret_key(secret_key: SecretKey, secp: &Secp256k1<Al
     PublicKey::from_secret_key(&secp, &secret_key)
 }
 
+/// BIP341 key-path output key `Q = P + H_TapTweak(P)G` of an internal key without script tree.
+/// `None` in the negligible cases that the tweak is not below the curve order or `Q` is infinity,
+/// for which BIP341 defines no output key.
+pub fn taproot_output_key(
+    internal_key: &XOnlyPublicKey,
+    secp: &Secp256k1<All>,
+) -> Option<XOnlyPublicKey> {
+    let tweak = hash_util::tagged_hash("TapTweak", &internal_key.serialize());
+    let tweak = Scalar::from_be_bytes(tweak).ok()?;
+    let (output_key, _parity) = internal_key.add_tweak(secp, &tweak).ok()?;
+    Some(output_key)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::key_util::get_public_key_from_secret_key;
+    use crate::key_util::{
+        get_public_key_from_private_key, get_public_key_from_secret_key, taproot_output_key,
+    };
     use hex_literal::hex;
     use num_bigint::BigUint;
     use num_traits::{CheckedMul, One};
@@ -73,4 +89,25 @@ mod tests {
             "792bfa55bf659967951b21060c05c250cd261ec3ea02704815bfb1c5ccc800fd"
         )
     }
+
+    #[test]
+    fn bip341_key_path_output_key() {
+        let secp = Secp256k1::new();
+        let public_key = get_public_key_from_private_key(
+            hex!("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa"),
+            &secp,
+        );
+        let (internal_key, _) = public_key.x_only_public_key();
+        assert_eq!(
+            internal_key.serialize(),
+            hex!("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d")
+        );
+
+        assert_eq!(
+            taproot_output_key(&internal_key, &secp)
+                .unwrap()
+                .serialize(),
+            hex!("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343")
+        );
+    }
 }
@@ -1,9 +1,25 @@
 pub use clap::parser;
 
 pub mod address_file;
+pub mod block_file;
 pub mod btc_address;
 pub mod collider;
+pub mod delta;
+pub mod descriptor;
+pub mod error;
+pub mod harvest;
 pub mod hash_util;
+pub mod inspect;
 pub mod key_util;
+pub mod network;
+pub mod normalize;
+pub mod script;
+pub mod script_template;
 pub mod search_space;
+pub mod serialize;
+pub mod session;
+pub mod targets;
+#[cfg(test)]
+mod test_util;
+pub mod utxo_snapshot;
 pub mod wif;
@@ -0,0 +1,121 @@
+use bech32::Hrp;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The bitcoin network addresses and WIFs are encoded for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// Base58Check version byte of P2PKH addresses
+    pub fn p2pkh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            _ => 0x6f,
+        }
+    }
+
+    /// Base58Check version byte of P2SH addresses
+    pub fn p2sh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            _ => 0xc4,
+        }
+    }
+
+    /// Version byte of private keys in WIF
+    pub fn wif_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x80,
+            _ => 0xef,
+        }
+    }
+
+    /// Human readable part of segwit addresses
+    pub fn hrp(&self) -> Hrp {
+        match self {
+            Network::Mainnet => bech32::hrp::BC,
+            Network::Testnet | Network::Signet => bech32::hrp::TB,
+            Network::Regtest => bech32::hrp::BCRT,
+        }
+    }
+
+    /// Message start bytes of the network, also written to UTXO snapshots. Testnet is testnet3 or
+    /// testnet4, signet the default signet.
+    pub fn message_starts(&self) -> &'static [[u8; 4]] {
+        match self {
+            Network::Mainnet => &[[0xf9, 0xbe, 0xb4, 0xd9]],
+            Network::Testnet => &[[0x0b, 0x11, 0x09, 0x07], [0x1c, 0x16, 0x3f, 0x28]],
+            Network::Signet => &[[0x0a, 0x03, 0xcf, 0x40]],
+            Network::Regtest => &[[0xfa, 0xbf, 0xb5, 0xda]],
+        }
+    }
+
+    /// Leading characters of base58 P2PKH addresses
+    pub fn p2pkh_prefixes(&self) -> &'static [char] {
+        match self {
+            Network::Mainnet => &['1'],
+            _ => &['m', 'n'],
+        }
+    }
+
+    /// Leading characters of base58 P2SH addresses
+    pub fn p2sh_prefixes(&self) -> &'static [char] {
+        match self {
+            Network::Mainnet => &['3'],
+            _ => &['2'],
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "bitcoin" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(format!(
+                "unknown network '{}', expected mainnet, testnet, signet or regtest",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parameterized_macro::parameterized;
+
+    #[parameterized(name = {"mainnet", "testnet", "signet", "regtest"})]
+    fn can_parse_network(name: &str) {
+        let network = Network::from_str(name).unwrap();
+        assert_eq!(name, network.to_string());
+    }
+
+    #[test]
+    fn unknown_network() {
+        assert!(Network::from_str("litecoin").is_err());
+    }
+}
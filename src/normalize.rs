@@ -0,0 +1,290 @@
+use crate::address_file;
+use crate::address_file::{AddressCount, AddressSource, LoadOptions, LoadReport, Strictness};
+use crate::btc_address::BTCAddressType;
+use crate::error::Error;
+use crate::network::Network;
+use crate::targets::Target;
+use primitive_types::H256;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+/// What normalizing address files read and wrote, written next to the normalized file so that
+/// runs can record which target list they used.
+#[derive(Debug)]
+pub struct Manifest {
+    pub output: String,
+    /// SHA256 of the normalized lines before compression
+    pub content_hash: H256,
+    pub network: Network,
+    pub files: Vec<String>,
+    /// Lines of the input files
+    pub report: LoadReport,
+    pub rejects: Option<String>,
+    pub targets: usize,
+    /// Lines of the normalized file
+    pub address_count: AddressCount,
+}
+
+/// Loads the address files, rejecting invalid lines, and writes every target once per type it is
+/// listed as: sorted by type and address, with canonical lower case bech32 addresses and hex
+/// public keys. With a balance column the balance of a target is on its first line, its other
+/// lines have balance 0. Invalid lines go to the rejects file unless `options` fail on them, lines
+/// of types the collider does not match go there as well.
+pub fn normalize(
+    sources: &[AddressSource],
+    options: &LoadOptions,
+    output: &str,
+    rejects: &str,
+) -> Result<Manifest, Error> {
+    let options = LoadOptions {
+        strictness: match &options.strictness {
+            Strictness::Skip => Strictness::Reject(rejects.to_string()),
+            strictness => strictness.clone(),
+        },
+        reject_unmatched: true,
+        ..options.clone()
+    };
+    let mut files = Vec::new();
+    for source in sources {
+        files.extend(source.files()?.into_iter().map(|(_, file)| file));
+    }
+    let (targets, report) = address_file::read_address_sources_with_report(sources, &options)?;
+
+    let mut lines: Vec<(BTCAddressType, String, Option<u64>)> = Vec::new();
+    for (target, info) in targets.iter() {
+        let mut addresses = target.addresses(info.address_types, options.network);
+        if let Target::PublicKey(..) = target {
+            // a public key revealed by a hash address stays a key, tagged with the address type
+            let tagged = target.tagged(info.address_types);
+            for ((address_type, address), tagged) in addresses.iter_mut().zip(tagged) {
+                if *address_type != BTCAddressType::P2PK {
+                    *address = tagged;
+                }
+            }
+        }
+        for (i, (address_type, address)) in addresses.into_iter().enumerate() {
+            let balance = info.balance.map(|balance| if i == 0 { balance } else { 0 });
+            lines.push((address_type, address, balance));
+        }
+    }
+    lines.sort_unstable_by(|a, b| (a.0 as u8, &a.1).cmp(&(b.0 as u8, &b.1)));
+
+    let mut address_count = AddressCount::default();
+    let mut hasher = Sha256::new();
+    address_file::create_address_file(output, |writer| {
+        for (address_type, address, balance) in &lines {
+            let line = match balance {
+                Some(balance) => format!("{}\t{}\n", address, balance),
+                None => format!("{}\n", address),
+            };
+            hasher.update(line.as_bytes());
+            writer
+                .write_all(line.as_bytes())
+                .map_err(|e| Error::io(output, e))?;
+            address_count.count(*address_type);
+        }
+        Ok(())
+    })?;
+
+    Ok(Manifest {
+        output: output.to_string(),
+        content_hash: H256::from_slice(&hasher.finalize()),
+        network: options.network,
+        files,
+        rejects: match options.strictness {
+            Strictness::Reject(rejects) => Some(rejects),
+            _ => None,
+        },
+        report,
+        targets: targets.len(),
+        address_count,
+    })
+}
+
+impl Manifest {
+    pub fn write(&self, file: &str) -> Result<(), Error> {
+        fs::write(file, self.to_string()).map_err(|e| Error::io(file, e))
+    }
+}
+
+/// The output file name without `.gz` and `.txt`, to name the rejects and manifest files after.
+pub fn output_stem(output: &str) -> &str {
+    let stem = output.strip_suffix(".gz").unwrap_or(output);
+    stem.strip_suffix(".txt").unwrap_or(stem)
+}
+
+impl Display for Manifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let input = &self.report.address_count;
+        writeln!(f, "Output: {}", self.output)?;
+        writeln!(f, "SHA256: {:x}", self.content_hash)?;
+        writeln!(f, "Network: {}", self.network)?;
+        writeln!(f, "Sources: {}", self.files.join(", "))?;
+        writeln!(f, "Input lines: {}", input.lines())?;
+        writeln!(f, "  Invalid: {}", input.err)?;
+        writeln!(f, "  Other: {}", input.misc)?;
+        writeln!(f, "  Below minimum balance: {}", input.below_min_balance)?;
+        writeln!(f, "  Duplicates: {}", self.report.duplicates)?;
+        if let Some(rejects) = &self.rejects {
+            writeln!(f, "Rejects: {}", rejects)?;
+        }
+        writeln!(f, "Targets: {}", self.targets)?;
+        let count = &self.address_count;
+        writeln!(f, "Lines: {}", count.lines())?;
+        writeln!(f, "  P2PKH: {}", count.p2pkh)?;
+        writeln!(f, "  P2SH: {}", count.p2sh)?;
+        writeln!(f, "  P2WPKH: {}", count.p2wpkh)?;
+        writeln!(f, "  P2WSH: {}", count.p2wsh)?;
+        writeln!(f, "  P2TR: {}", count.p2tr)?;
+        writeln!(f, "  P2PK: {}", count.p2pk)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address_file::{AddressSource, Columns, LoadOptions};
+    use crate::hash_util;
+    use crate::normalize::{normalize, output_stem};
+    use crate::test_util::temp_dir;
+    use flate2::read::GzDecoder;
+    use parameterized_macro::parameterized;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn normalizes_address_list() {
+        let dir = temp_dir("normalize-list");
+        let input = dir.join("input.txt");
+        let output = dir.join("normalized.txt.gz");
+        let rejects = dir.join("normalized.rejects.txt");
+        fs::write(
+            &input,
+            "  3N5i3Vs9UMyjYbBCFNQqU3ybSuDepX7oT3\n\
+            BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4\n\
+            1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm\n\
+            1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZX\n\
+            bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4 \n\
+            p2pkh:751e76e8199196d454941c45d1b3a323f1433bd6\n\
+            not an address\n",
+        )
+        .unwrap();
+        let sources: Vec<AddressSource> = vec![input.to_str().unwrap().parse().unwrap()];
+        let (output, rejects) = (output.to_str().unwrap(), rejects.to_str().unwrap());
+
+        let manifest = normalize(&sources, &LoadOptions::default(), output, rejects).unwrap();
+        let mut lines = String::new();
+        GzDecoder::new(fs::File::open(output).unwrap())
+            .read_to_string(&mut lines)
+            .unwrap();
+        let rejected = fs::read_to_string(rejects).unwrap();
+        let again = normalize(&sources, &LoadOptions::default(), output, rejects).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            vec![
+                "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+                "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+                "3N5i3Vs9UMyjYbBCFNQqU3ybSuDepX7oT3",
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            ],
+            lines.lines().collect::<Vec<_>>()
+        );
+        assert_eq!(2, rejected.lines().count());
+        assert!(rejected.contains(":4\t1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZX\t"));
+        assert!(rejected.contains(":7\tnot an address\tMISC is not an address type"));
+
+        assert_eq!(7, manifest.report.address_count.lines());
+        assert_eq!(1, manifest.report.address_count.err);
+        assert_eq!(1, manifest.report.address_count.misc);
+        assert_eq!(1, manifest.report.duplicates);
+        assert_eq!(Some(rejects.to_string()), manifest.rejects);
+        assert_eq!(3, manifest.targets);
+        assert_eq!(4, manifest.address_count.lines());
+        assert_eq!(2, manifest.address_count.p2pkh);
+        assert_eq!(hash_util::sha256(lines.as_bytes()), manifest.content_hash);
+        assert_eq!(manifest.content_hash, again.content_hash);
+    }
+
+    #[test]
+    fn keeps_balance_on_first_line_of_target() {
+        let dir = temp_dir("normalize-balance");
+        let input = dir.join("input.txt");
+        let output = dir.join("normalized.txt");
+        let rejects = dir.join("normalized.rejects.txt");
+        fs::write(
+            &input,
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t0\n\
+            1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n",
+        )
+        .unwrap();
+        let options = LoadOptions {
+            columns: Columns {
+                balance: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        normalize(
+            &[input.to_str().unwrap().parse().unwrap()],
+            &options,
+            output.to_str().unwrap(),
+            rejects.to_str().unwrap(),
+        )
+        .unwrap();
+        let lines = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n\
+            bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\t0\n",
+            lines
+        );
+    }
+
+    #[test]
+    fn keeps_larger_balance_of_duplicated_line() {
+        let dir = temp_dir("normalize-duplicate");
+        let input = dir.join("input.txt");
+        let output = dir.join("normalized.txt");
+        let rejects = dir.join("normalized.rejects.txt");
+        fs::write(
+            &input,
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n\
+            1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n\
+            1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t3000\n",
+        )
+        .unwrap();
+        let options = LoadOptions {
+            columns: Columns {
+                balance: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let manifest = normalize(
+            &[input.to_str().unwrap().parse().unwrap()],
+            &options,
+            output.to_str().unwrap(),
+            rejects.to_str().unwrap(),
+        )
+        .unwrap();
+        let lines = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH\t5000\n", lines);
+        assert_eq!(2, manifest.report.duplicates);
+    }
+
+    #[parameterized(output = {
+    "targets.txt.gz", "targets.txt", "targets.gz", "out/targets",
+    }, expected = {
+    "targets", "targets", "targets", "out/targets",
+    })]
+    fn strips_extensions_from_output(output: &str, expected: &str) {
+        assert_eq!(expected, output_stem(output));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::btc_address::BTCAddressType;
+use crate::targets::Target;
+use primitive_types::{H160, H256};
+use secp256k1::PublicKey;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_DUP: u8 = 0x76;
+const OP_HASH160: u8 = 0xa9;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_CHECKSIG: u8 = 0xac;
+
+/// The address type of an output script with the hash, output key or public key it pays to.
+/// Scripts of other types, and P2PK outputs with a public key not on the curve, are `MISC`.
+pub fn output_program(script: &[u8]) -> (BTCAddressType, Option<&[u8]>) {
+    match script {
+        [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG] if hash.len() == 20 => {
+            (BTCAddressType::P2PKH, Some(hash))
+        }
+        [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20 => {
+            (BTCAddressType::P2SH, Some(hash))
+        }
+        [OP_0, 20, hash @ ..] if hash.len() == 20 => (BTCAddressType::P2WPKH, Some(hash)),
+        [OP_0, 32, hash @ ..] if hash.len() == 32 => (BTCAddressType::P2WSH, Some(hash)),
+        [OP_1, 32, key @ ..] if key.len() == 32 => (BTCAddressType::P2TR, Some(key)),
+        [33 | 65, key @ .., OP_CHECKSIG]
+            if key.len() == script[0] as usize && PublicKey::from_slice(key).is_ok() =>
+        {
+            (BTCAddressType::P2PK, Some(key))
+        }
+        _ => (BTCAddressType::MISC, None),
+    }
+}
+
+/// The address type of an output script and what the collider matches it by, `MISC` scripts have
+/// no target.
+pub fn output_target(script: &[u8]) -> (BTCAddressType, Option<Target>) {
+    let (address_type, program) = output_program(script);
+    let target = program.and_then(|program| match address_type {
+        BTCAddressType::P2PKH | BTCAddressType::P2WPKH => {
+            Some(Target::PubkeyHash(H160::from_slice(program)))
+        }
+        BTCAddressType::P2SH => Some(Target::ScriptHash(H160::from_slice(program))),
+        BTCAddressType::P2WSH => Some(Target::WitnessScriptHash(H256::from_slice(program))),
+        BTCAddressType::P2TR => Some(Target::OutputKey(H256::from_slice(program))),
+        BTCAddressType::P2PK => PublicKey::from_slice(program)
+            .ok()
+            .map(|public_key| Target::PublicKey(public_key, program.len() == 33)),
+        _ => None,
+    });
+    (address_type, target)
+}
+
+/// The data pushes of a script sig, `None` if it has other operations than pushes.
+pub fn pushes(script: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut pushes = Vec::new();
+    let mut rest = script;
+    while let [opcode, tail @ ..] = rest {
+        let (len, tail) = match *opcode {
+            OP_0 => (0, tail),
+            len @ 1..OP_PUSHDATA1 => (len as usize, tail),
+            OP_PUSHDATA1 => match tail {
+                [len, tail @ ..] => (*len as usize, tail),
+                _ => return None,
+            },
+            OP_PUSHDATA2 => match tail {
+                [a, b, tail @ ..] => (u16::from_le_bytes([*a, *b]) as usize, tail),
+                _ => return None,
+            },
+            OP_PUSHDATA4 => match tail {
+                [a, b, c, d, tail @ ..] => (u32::from_le_bytes([*a, *b, *c, *d]) as usize, tail),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if tail.len() < len {
+            return None;
+        }
+        let (data, tail) = tail.split_at(len);
+        pushes.push(data);
+        rest = tail;
+    }
+    Some(pushes)
+}
+
+/// The public key an input reveals by spending a P2PKH output, with signature and public key in
+/// the script sig, or a P2WPKH or P2SH-P2WPKH output, with signature and public key as witness.
+pub fn revealed_public_key<'a>(script_sig: &'a [u8], witness: &'a [Vec<u8>]) -> Option<&'a [u8]> {
+    let key = match witness {
+        [] => match pushes(script_sig)?.as_slice() {
+            [_signature, key] => *key,
+            _ => return None,
+        },
+        [_signature, key] if key.len() == 33 => key.as_slice(),
+        _ => return None,
+    };
+    match key {
+        [0x02 | 0x03, ..] if key.len() == 33 => {}
+        [0x04, ..] if key.len() == 65 => {}
+        _ => return None,
+    }
+    PublicKey::from_slice(key).ok().map(|_| key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_address;
+    use crate::btc_address::BTCAddressType;
+    use crate::script::{output_target, pushes, revealed_public_key};
+    use crate::targets::Target;
+    use hex_literal::hex;
+    use parameterized_macro::parameterized;
+    use primitive_types::H160;
+
+    #[parameterized(script = {
+    &hex!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+    &hex!("a914bcfeb728b584253d5f3f70bcb780e9ef218a68f487"),
+    &hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+    &hex!("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+    &hex!("5120da4710964f7852695de2da025290e24af6d8c281de5a0b902b7135fd9fd74d21"),
+    &hex!("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac"),
+    &hex!("6a0b68656c6c6f20776f726c64"),
+    &hex!("76a913751e76e8199196d454941c45d1b3a323f1433b88ac"),
+    &hex!("210200000000000000000000000000000000000000000000000000000000000000ac"),
+    }, expected = {
+    BTCAddressType::P2PKH,
+    BTCAddressType::P2SH,
+    BTCAddressType::P2WPKH,
+    BTCAddressType::P2WSH,
+    BTCAddressType::P2TR,
+    BTCAddressType::P2PK,
+    BTCAddressType::MISC,
+    BTCAddressType::MISC,
+    BTCAddressType::MISC,
+    })]
+    fn can_classify_output_script(script: &[u8], expected: BTCAddressType) {
+        let (address_type, target) = output_target(script);
+        assert_eq!(expected, address_type);
+        assert_eq!(expected != BTCAddressType::MISC, target.is_some());
+    }
+
+    #[test]
+    fn decodes_pubkey_hash() {
+        let (_, target) =
+            output_target(&hex!("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"));
+        let expected: H160 = hex!("751e76e8199196d454941c45d1b3a323f1433bd6").into();
+        assert_eq!(Some(Target::PubkeyHash(expected)), target);
+    }
+
+    #[test]
+    fn decodes_uncompressed_public_key() {
+        let script = hex!(
+            "41"
+            "04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5"
+            "1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a"
+            "ac"
+        );
+        let (expected, _) = btc_address::p2pk_output_to_public_key(
+            "04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5\
+            1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a",
+        )
+        .unwrap();
+        assert_eq!(
+            (
+                BTCAddressType::P2PK,
+                Some(Target::PublicKey(expected, false))
+            ),
+            output_target(&script)
+        );
+    }
+
+    #[test]
+    fn can_read_pushes() {
+        let script = hex!("00" "03aabbcc" "4c02ddee" "4d0100ff");
+        let expected: Vec<&[u8]> = vec![&[], &hex!("aabbcc"), &hex!("ddee"), &hex!("ff")];
+        assert_eq!(Some(expected), pushes(&script));
+        // OP_DUP
+        assert_eq!(None, pushes(&hex!("76")));
+        // push of 3 bytes with 2 left
+        assert_eq!(None, pushes(&hex!("03aabb")));
+    }
+
+    #[test]
+    fn finds_revealed_public_key() {
+        let key = hex!("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        let signature = [0x30; 71];
+        let mut script_sig = vec![71];
+        script_sig.extend(signature);
+        script_sig.push(33);
+        script_sig.extend(key);
+        assert_eq!(Some(&key[..]), revealed_public_key(&script_sig, &[]));
+
+        let witness = vec![signature.to_vec(), key.to_vec()];
+        assert_eq!(Some(&key[..]), revealed_public_key(&[], &witness));
+
+        // P2PK spend with the signature only
+        assert_eq!(None, revealed_public_key(&script_sig[..72], &[]));
+        // 33 bytes that are not a public key, there is no point with x = 0
+        let mut not_a_key = vec![0x02];
+        not_a_key.extend([0; 32]);
+        let witness = vec![signature.to_vec(), not_a_key];
+        assert_eq!(None, revealed_public_key(&[], &witness));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::hash_util;
+use crate::targets::Target;
+use primitive_types::H160;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+const OP_0: u8 = 0x00;
+const OP_DUP: u8 = 0x76;
+const OP_HASH160: u8 = 0xa9;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_CHECKSIG: u8 = 0xac;
+
+/// A script over a single public key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyScript {
+    /// `<pubkey> OP_CHECKSIG`
+    Pk,
+    /// `OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG`
+    Pkh,
+    /// The P2WPKH program `OP_0 <pubkey hash>`, only valid as P2SH redeem script
+    Wpkh,
+}
+
+impl KeyScript {
+    fn script(&self, public_key: &[u8], pubkey_hash: &H160) -> Vec<u8> {
+        match self {
+            KeyScript::Pk => {
+                let mut script = Vec::with_capacity(public_key.len() + 2);
+                script.push(public_key.len() as u8);
+                script.extend_from_slice(public_key);
+                script.push(OP_CHECKSIG);
+                script
+            }
+            KeyScript::Pkh => {
+                let mut script = vec![OP_DUP, OP_HASH160, 20];
+                script.extend_from_slice(pubkey_hash.as_bytes());
+                script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+                script
+            }
+            KeyScript::Wpkh => {
+                let mut script = vec![OP_0, 20];
+                script.extend_from_slice(pubkey_hash.as_bytes());
+                script
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            KeyScript::Pk => "pk",
+            KeyScript::Pkh => "pkh",
+            KeyScript::Wpkh => "wpkh",
+        }
+    }
+}
+
+/// How an output commits to a single key script, written like an output descriptor without key,
+/// e.g. `wsh(pk)`. The collider computes the commitment of every enabled template for each
+/// candidate public key and matches it against the script hash targets.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScriptTemplate {
+    /// P2SH of the script, `sh(<script>)`
+    Sh(KeyScript),
+    /// P2WSH of the script, `wsh(<script>)`
+    Wsh(KeyScript),
+    /// P2WSH of the script nested in P2SH, `sh(wsh(<script>))`
+    ShWsh(KeyScript),
+}
+
+impl ScriptTemplate {
+    /// P2SH-P2WPKH, the templates the collider uses unless configured otherwise
+    pub const DEFAULT: &'static [ScriptTemplate] = &[ScriptTemplate::Sh(KeyScript::Wpkh)];
+
+    /// The script hash or witness script hash target an output of the template pays to for the
+    /// serialized public key, given with its HASH160. `None` for uncompressed keys in segwit
+    /// scripts, which are not standard.
+    pub fn commitment(&self, public_key: &[u8], pubkey_hash: &H160) -> Option<Target> {
+        if self.is_segwit() && public_key.len() != 33 {
+            return None;
+        }
+        Some(match self {
+            ScriptTemplate::Sh(script) => {
+                Target::ScriptHash(hash_util::hash160(&script.script(public_key, pubkey_hash)))
+            }
+            ScriptTemplate::Wsh(script) => Target::WitnessScriptHash(hash_util::sha256(
+                &script.script(public_key, pubkey_hash),
+            )),
+            ScriptTemplate::ShWsh(script) => {
+                let mut program = vec![OP_0, 32];
+                program.extend_from_slice(
+                    hash_util::sha256(&script.script(public_key, pubkey_hash)).as_bytes(),
+                );
+                Target::ScriptHash(hash_util::hash160(&program))
+            }
+        })
+    }
+
+    fn is_segwit(&self) -> bool {
+        !matches!(self, ScriptTemplate::Sh(KeyScript::Pk | KeyScript::Pkh))
+    }
+}
+
+impl FromStr for ScriptTemplate {
+    type Err = String;
+
+    /// `sh(<script>)`, `wsh(<script>)` or `sh(wsh(<script>))` with script `pk`, `pkh`, or `wpkh`
+    /// in P2SH only
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key_script = |name: &str| match name {
+            "pk" => Some(KeyScript::Pk),
+            "pkh" => Some(KeyScript::Pkh),
+            "wpkh" => Some(KeyScript::Wpkh),
+            _ => None,
+        };
+        let inner = |s: &str, wrapper: &str| {
+            s.strip_prefix(wrapper)
+                .and_then(|s| s.strip_prefix('('))
+                .and_then(|s| s.strip_suffix(')'))
+                .map(str::to_string)
+        };
+        let template = if let Some(nested) = inner(s, "sh").and_then(|s| inner(&s, "wsh")) {
+            key_script(&nested).map(ScriptTemplate::ShWsh)
+        } else if let Some(script) = inner(s, "sh") {
+            key_script(&script).map(ScriptTemplate::Sh)
+        } else if let Some(script) = inner(s, "wsh") {
+            key_script(&script).map(ScriptTemplate::Wsh)
+        } else {
+            None
+        };
+        match template {
+            Some(ScriptTemplate::Wsh(KeyScript::Wpkh) | ScriptTemplate::ShWsh(KeyScript::Wpkh)) => {
+                Err(format!("wpkh is only valid inside sh, got {}", s))
+            }
+            Some(template) => Ok(template),
+            None => Err(format!(
+                "Unknown script template {}, expected sh(<script>), wsh(<script>) or sh(wsh(<script>)) of pk or pkh, or sh(wpkh)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ScriptTemplate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptTemplate::Sh(script) => write!(f, "sh({})", script.name()),
+            ScriptTemplate::Wsh(script) => write!(f, "wsh({})", script.name()),
+            ScriptTemplate::ShWsh(script) => write!(f, "sh(wsh({}))", script.name()),
+        }
+    }
+}
+
+/// Written like the template, so that collision reports show `Script(wsh(pk))`.
+impl Debug for ScriptTemplate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script_template::ScriptTemplate;
+    use crate::targets::Target;
+    use crate::{btc_address, hash_util};
+    use hex_literal::hex;
+    use parameterized_macro::parameterized;
+
+    const KEY_1: [u8; 33] =
+        hex!("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+
+    #[parameterized(template = {
+    "sh(pk)", "sh(pkh)", "sh(wpkh)", "sh(wsh(pk))",
+    }, expected = {
+    "23b0ad3477f2178bc0b3eed26e4e6316f4e83aa1",
+    "cd7b44d0b03f2d026d1e586d7ae18903b0d385f6",
+    "bcfeb728b584253d5f3f70bcb780e9ef218a68f4",
+    "e4300531190587e3880d4c3004f5355d88ff928d",
+    })]
+    fn computes_script_hash(template: &str, expected: &str) {
+        let template: ScriptTemplate = template.parse().unwrap();
+        let expected = btc_address::hex_to_160_bit_hash(expected).unwrap();
+        assert_eq!(
+            Some(Target::ScriptHash(expected)),
+            template.commitment(&KEY_1, &hash_util::hash160(&KEY_1))
+        );
+    }
+
+    #[parameterized(template = {"wsh(pk)", "wsh(pkh)"}, expected = {
+    "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+    "3f4ae1eb3e75f3578491811d27eb59e23a512cf90c73b13c6acd4499f7c4d28b",
+    })]
+    fn computes_witness_script_hash(template: &str, expected: &str) {
+        let template: ScriptTemplate = template.parse().unwrap();
+        let expected = btc_address::hex_to_256_bit_hash(expected).unwrap();
+        assert_eq!(
+            Some(Target::WitnessScriptHash(expected)),
+            template.commitment(&KEY_1, &hash_util::hash160(&KEY_1))
+        );
+    }
+
+    #[test]
+    fn uncompressed_keys_only_in_legacy_scripts() {
+        let uncompressed = hex!(
+            "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+            "483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"
+        );
+        let expected =
+            btc_address::hex_to_160_bit_hash("31edc23bdafda4639e669f89ad6b2318dd79d032").unwrap();
+        let sh_pk: ScriptTemplate = "sh(pk)".parse().unwrap();
+        assert_eq!(
+            Some(Target::ScriptHash(expected)),
+            sh_pk.commitment(&uncompressed, &hash_util::hash160(&uncompressed))
+        );
+        let wsh_pk: ScriptTemplate = "wsh(pk)".parse().unwrap();
+        assert_eq!(
+            None,
+            wsh_pk.commitment(&uncompressed, &hash_util::hash160(&uncompressed))
+        );
+    }
+
+    #[parameterized(template = {
+    "sh(pk)", "sh(pkh)", "sh(wpkh)", "wsh(pk)", "wsh(pkh)", "sh(wsh(pk))", "sh(wsh(pkh))",
+    })]
+    fn can_parse_template(template: &str) {
+        let parsed: ScriptTemplate = template.parse().unwrap();
+        assert_eq!(template, parsed.to_string());
+    }
+
+    #[parameterized(template = {"pk", "wsh(wpkh)", "sh(wsh(wpkh))", "sh(multi)", "sh(pk", "tr(pk)"})]
+    fn rejects_invalid_template(template: &str) {
+        assert!(template.parse::<ScriptTemplate>().is_err());
+    }
+}
@@ -1,94 +1,271 @@
 use super::SearchSpace;
-use crate::search_space::SearchSpaceProvider;
-use log::{debug, info};
+use crate::search_space::shard::{Shard, ShardedRange};
+use crate::search_space::{
+    IssuedSearchSpace, ParseSearchSpaceError, SearchSpaceError, SearchSpaceProvider,
+};
+use log::{debug, info, warn};
 use num_bigint::{BigUint, RandBigInt};
+use num_traits::Num;
 use std::collections::BTreeSet;
 use std::fs::File;
 use std::io;
 use std::io::{BufRead, BufWriter, Write};
-use std::ops::Add;
+use std::ops::Bound::{Excluded, Unbounded};
+use std::str::FromStr;
+
+/// Where to start when no search space is done yet.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum StartPolicy {
+    #[default]
+    Random,
+    Fixed(BigUint),
+    /// Start of the range when scanning up, end of the range when scanning down
+    RangeStart,
+}
+
+impl FromStr for StartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(StartPolicy::Random),
+            "range-start" => Ok(StartPolicy::RangeStart),
+            _ => BigUint::from_str_radix(s, 16)
+                .map(StartPolicy::Fixed)
+                .map_err(|_| {
+                    format!(
+                        "Unknown start {}, expected random, range-start or a hex key",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScanDirection {
+    #[default]
+    Up,
+    Down,
+    /// Alternate between up and down
+    Bidirectional,
+}
+
+impl FromStr for ScanDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(ScanDirection::Up),
+            "down" => Ok(ScanDirection::Down),
+            "both" => Ok(ScanDirection::Bidirectional),
+            _ => Err(format!(
+                "Unknown direction {}, expected up, down or both",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanOptions {
+    pub start: StartPolicy,
+    pub direction: ScanDirection,
+    /// Search the holes between done search spaces before extending
+    pub fill_gaps: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            start: StartPolicy::default(),
+            direction: ScanDirection::default(),
+            fill_gaps: true,
+        }
+    }
+}
 
 pub struct FileSearchSpaceProvider {
     done: BTreeSet<SearchSpace>,
     pending: BTreeSet<SearchSpace>,
-    interval: BigUint,
+    range: ShardedRange,
+    options: ScanOptions,
+    scan_down: bool,
     file: String,
 }
 
 impl FileSearchSpaceProvider {
-    pub fn new(file: &'static str) -> FileSearchSpaceProvider {
+    pub fn new(file: &str) -> Result<FileSearchSpaceProvider, ParseSearchSpaceError> {
+        Self::with_shard(file, Shard::default())
+    }
+
+    pub fn with_shard(
+        file: &str,
+        shard: Shard,
+    ) -> Result<FileSearchSpaceProvider, ParseSearchSpaceError> {
+        Self::with_options(file, shard, ScanOptions::default())
+    }
+
+    /// Reads the done search spaces from `file`, failing on the first line that is not a valid search space.
+    /// Empty ranges like the seed line `8000…-8000…` are skipped, with a random start the scan starts
+    /// at the first of them instead.
+    pub fn with_options(
+        file: &str,
+        shard: Shard,
+        mut options: ScanOptions,
+    ) -> Result<FileSearchSpaceProvider, ParseSearchSpaceError> {
         let mut done = BTreeSet::new();
+        let mut seed = None;
 
-        match File::open(&file) {
+        match File::open(file) {
             Ok(f) => {
-                for line in io::BufReader::new(f).lines() {
-                    let search_space = SearchSpace::from_line(&line.unwrap());
+                for (i, line) in io::BufReader::new(f).lines().enumerate() {
+                    let line = line.map_err(|_| ParseSearchSpaceError {
+                        line: Some(i + 1),
+                        input: String::new(),
+                        error: SearchSpaceError::InvalidFormat,
+                    })?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let search_space: SearchSpace = match line.parse() {
+                        Ok(search_space) => search_space,
+                        Err(e) => match empty_range_position(&line) {
+                            Some(position) => {
+                                warn!("Skipping empty search space on line {} of {}", i + 1, file);
+                                seed.get_or_insert(position);
+                                continue;
+                            }
+                            None => return Err(e.at_line(i + 1)),
+                        },
+                    };
                     done.insert(search_space);
                 }
             }
             Err(_) => info!("No existing search space file found."),
         }
+        if let (StartPolicy::Random, Some(seed)) = (&options.start, seed) {
+            options.start = StartPolicy::Fixed(seed);
+        }
 
         let interval: u64 = 1_000_000;
-        FileSearchSpaceProvider {
+        let range = ShardedRange::new(SearchSpace::key_space(), BigUint::from(interval), shard)
+            .map_err(|error| ParseSearchSpaceError {
+                line: None,
+                input: SearchSpace::key_space().to_string(),
+                error,
+            })?;
+        Ok(FileSearchSpaceProvider {
             done,
             pending: BTreeSet::new(),
-            interval: BigUint::from(interval),
+            range,
+            options,
+            scan_down: false,
             file: file.to_string(),
+        })
+    }
+
+    /// The done and pending search spaces merged into disjoint, ascending runs.
+    fn covered_runs(&self) -> Vec<SearchSpace> {
+        let mut covered: Vec<&SearchSpace> = self.done.iter().chain(self.pending.iter()).collect();
+        covered.sort();
+
+        let mut runs: Vec<SearchSpace> = Vec::new();
+        for search_space in covered {
+            match runs.last_mut() {
+                Some(last) if last.end_exclusive >= search_space.start_inclusive => {
+                    last.end_exclusive = last
+                        .end_exclusive
+                        .clone()
+                        .max(search_space.end_exclusive.clone());
+                }
+                _ => runs.push(search_space.clone()),
+            }
         }
+        runs
     }
 
-    fn add(&mut self, search_space: &SearchSpace) {
-        let mut merge_lower = false;
-        let mut merge_higher = false;
+    /// The first owned keys in a hole between two runs, searched from the lowest hole upwards.
+    fn next_in_gap(&self, runs: &[SearchSpace], direction: ScanDirection) -> Option<SearchSpace> {
+        for pair in runs.windows(2) {
+            let gap_start = &pair[0].end_exclusive;
+            let gap_end = &pair[1].start_inclusive;
 
-        let mut lower_search_space = Option::None;
-        for s in self.done.iter() {
-            if s.cmp(&search_space).is_lt() {
-                lower_search_space = Option::Some(s.clone());
+            let candidate = match direction {
+                ScanDirection::Down => self
+                    .range
+                    .prev_owned(gap_end)
+                    .filter(|c| &c.end_exclusive > gap_start)
+                    .map(|mut c| {
+                        c.start_inclusive = c.start_inclusive.max(gap_start.clone());
+                        c
+                    }),
+                _ => self
+                    .range
+                    .next_owned(gap_start)
+                    .filter(|c| &c.start_inclusive < gap_end)
+                    .map(|mut c| {
+                        c.end_exclusive = c.end_exclusive.min(gap_end.clone());
+                        c
+                    }),
+            };
+            if candidate.is_some() {
+                return candidate;
             }
         }
+        None
+    }
 
-        if lower_search_space.is_some()
-            && lower_search_space.clone().unwrap().can_merge(&search_space)
-        {
-            self.done.remove(&lower_search_space.clone().unwrap());
-            merge_lower = true;
+    /// The first owned keys beyond the covered runs in `direction`, or from the start point if nothing is covered.
+    fn next_beyond(&self, runs: &[SearchSpace], direction: ScanDirection) -> Option<SearchSpace> {
+        match (runs.first(), runs.last(), direction) {
+            (_, Some(last), ScanDirection::Up) => self.range.next_owned(&last.end_exclusive),
+            (Some(first), _, ScanDirection::Down) => self.range.prev_owned(&first.start_inclusive),
+            (_, _, ScanDirection::Up) => match &self.options.start {
+                StartPolicy::Random => Some(self.random_owned_chunk()),
+                StartPolicy::Fixed(key) => self.range.next_owned(key),
+                StartPolicy::RangeStart => {
+                    self.range.next_owned(&self.range.range().start_inclusive)
+                }
+            },
+            (_, _, _) => match &self.options.start {
+                StartPolicy::Random => Some(self.random_owned_chunk()),
+                StartPolicy::Fixed(key) => self.range.prev_owned(key),
+                StartPolicy::RangeStart => self.range.prev_owned(&self.range.range().end_exclusive),
+            },
         }
+    }
+
+    fn random_owned_chunk(&self) -> SearchSpace {
+        let mut rng = rand::thread_rng();
+        let nth = rng.gen_biguint_below(&self.range.owned_chunk_count());
+        self.range.owned_chunk(&nth)
+    }
+
+    fn add(&mut self, search_space: &SearchSpace) {
+        let mut merged = search_space.clone();
 
-        let mut higher_search_space = Option::None;
-        for s in self.done.iter() {
-            if s.cmp(&search_space).is_gt() {
-                higher_search_space = Option::Some(s.clone());
-                break;
+        let lower_search_space = self.done.range(..search_space).next_back().cloned();
+        if let Some(lower_search_space) = lower_search_space {
+            if let Ok(m) = lower_search_space.try_merge(&merged) {
+                self.done.remove(&lower_search_space);
+                merged = m;
             }
         }
 
-        if higher_search_space.is_some()
-            && higher_search_space
-                .clone()
-                .unwrap()
-                .can_merge(&search_space)
-        {
-            self.done.remove(&higher_search_space.clone().unwrap());
-            merge_higher = true;
+        let higher_search_space = self
+            .done
+            .range((Excluded(search_space), Unbounded))
+            .next()
+            .cloned();
+        if let Some(higher_search_space) = higher_search_space {
+            if let Ok(m) = higher_search_space.try_merge(&merged) {
+                self.done.remove(&higher_search_space);
+                merged = m;
+            }
         }
 
-        if merge_lower && merge_higher {
-            let merged = SearchSpace {
-                start_inclusive: lower_search_space.unwrap().start_inclusive.clone(),
-                end_exclusive: higher_search_space.unwrap().end_exclusive.clone(),
-            };
-            self.done.insert(merged);
-        } else if merge_lower {
-            let merged = lower_search_space.unwrap().merge(search_space);
-            self.done.insert(merged);
-        } else if merge_higher {
-            let merged = higher_search_space.unwrap().merge(search_space);
-            self.done.insert(merged);
-        } else {
-            self.done.insert(search_space.clone());
-        }
+        self.done.insert(merged);
     }
 
     fn write_to_file(&self) {
@@ -101,56 +278,77 @@ impl FileSearchSpaceProvider {
     }
 }
 
+/// The position of a line `<start>-<end>` with `start` equal to `end`.
+fn empty_range_position(line: &str) -> Option<BigUint> {
+    let (start, end) = line.trim().split_once('-')?;
+    let start = BigUint::from_str_radix(start, 16).ok()?;
+    (start == BigUint::from_str_radix(end, 16).ok()?).then_some(start)
+}
+
 impl SearchSpaceProvider for FileSearchSpaceProvider {
-    fn next(&mut self) -> SearchSpace {
-        let start_inclusive;
-
-        if !self.pending.is_empty() {
-            let last = self.pending.last().unwrap();
-            start_inclusive = last.end_exclusive.clone();
-        } else if !self.done.is_empty() {
-            let first = self.done.first().unwrap();
-            start_inclusive = first.end_exclusive.clone();
+    fn next(&mut self) -> IssuedSearchSpace {
+        let direction = match self.options.direction {
+            ScanDirection::Bidirectional => {
+                let direction = if self.scan_down {
+                    ScanDirection::Down
+                } else {
+                    ScanDirection::Up
+                };
+                self.scan_down = !self.scan_down;
+                direction
+            }
+            direction => direction,
+        };
+        let opposite = match direction {
+            ScanDirection::Down => ScanDirection::Up,
+            _ => ScanDirection::Down,
+        };
+
+        let runs = self.covered_runs();
+        let search_space = (if self.options.fill_gaps {
+            self.next_in_gap(&runs, direction)
         } else {
-            let mut rng = rand::thread_rng();
-            start_inclusive = rng.gen_biguint(256);
-        }
+            None
+        })
+        .or_else(|| self.next_beyond(&runs, direction))
+        .or_else(|| self.next_beyond(&runs, opposite))
+        .or_else(|| self.next_in_gap(&runs, direction))
+        .unwrap_or_else(|| panic!("No keys left to search in shard {}", self.range.shard()));
 
-        let search_space = SearchSpace {
-            start_inclusive: start_inclusive.clone(),
-            end_exclusive: start_inclusive.clone().add(&self.interval),
-        };
         self.pending.insert(search_space.clone());
         debug!("Created next search space {}", search_space);
-        search_space
+        search_space.into()
     }
 
-    fn done(&mut self, search_space: &SearchSpace) {
-        if !self.pending.remove(&search_space) {
+    fn done(&mut self, issued: &IssuedSearchSpace) {
+        let search_space = &issued.search_space;
+        if !self.pending.remove(search_space) {
             panic!("Could not remove from pending");
         }
 
-        self.add(&search_space);
-
-        if self.done.len() == 1 {
-            self.write_to_file();
-        }
+        self.add(search_space);
+        self.write_to_file();
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::search_space::file_search_space_provider::FileSearchSpaceProvider;
-    use crate::search_space::SearchSpaceProvider;
+    use crate::search_space::file_search_space_provider::{
+        FileSearchSpaceProvider, ScanDirection, ScanOptions, StartPolicy,
+    };
+    use crate::search_space::shard::Shard;
+    use crate::search_space::{SearchSpace, SearchSpaceError, SearchSpaceProvider};
+    use num_bigint::BigUint;
     use num_traits::ToPrimitive;
+    use parameterized_macro::parameterized;
     use std::fs::File;
     use std::io::Write;
 
     #[test]
     fn can_read_from_file() {
-        let mut prov = FileSearchSpaceProvider::new("test/space.existing.txt");
+        let mut prov = FileSearchSpaceProvider::new("test/space.existing.txt").unwrap();
 
-        let search_space = prov.next();
+        let search_space = prov.next().search_space;
         assert_eq!(10, search_space.start_inclusive.to_u64().unwrap());
         assert_eq!(1_000_010, search_space.end_exclusive.to_u64().unwrap());
     }
@@ -162,11 +360,11 @@ mod tests {
         let mut f = File::create(&file).unwrap();
         f.write("4-b".as_bytes()).unwrap();
 
-        let mut prov = FileSearchSpaceProvider::new(&file);
-        let search_space = prov.next();
-        assert_eq!("000000000000000000000000000000000000000000000000000000000000000b-00000000000000000000000000000000000000000000000000000000000f424b", search_space.to_string());
+        let mut prov = FileSearchSpaceProvider::new(&file).unwrap();
+        let issued = prov.next();
+        assert_eq!("000000000000000000000000000000000000000000000000000000000000000b-00000000000000000000000000000000000000000000000000000000000f424b", issued.search_space.to_string());
 
-        prov.done(&search_space);
+        prov.done(&issued);
 
         let file_content = std::fs::read_to_string(&file).unwrap();
         let lines: Vec<&str> = file_content.lines().collect();
@@ -179,12 +377,12 @@ mod tests {
         let file = "test/space.merge.txt";
         std::fs::write(&file, "4-b").unwrap();
 
-        let mut p = FileSearchSpaceProvider::new(file);
+        let mut p = FileSearchSpaceProvider::new(file).unwrap();
         let s1 = p.next();
-        assert_eq!("000000000000000000000000000000000000000000000000000000000000000b-00000000000000000000000000000000000000000000000000000000000f424b", s1.to_string());
+        assert_eq!("000000000000000000000000000000000000000000000000000000000000000b-00000000000000000000000000000000000000000000000000000000000f424b", s1.search_space.to_string());
 
         let s2 = p.next();
-        assert_eq!("00000000000000000000000000000000000000000000000000000000000f424b-00000000000000000000000000000000000000000000000000000000001e848b", s2.to_string());
+        assert_eq!("00000000000000000000000000000000000000000000000000000000000f424b-00000000000000000000000000000000000000000000000000000000001e848b", s2.search_space.to_string());
 
         p.done(&s2);
         p.write_to_file();
@@ -200,4 +398,148 @@ mod tests {
         assert_eq!(1, lines.len());
         assert_eq!("0000000000000000000000000000000000000000000000000000000000000004-00000000000000000000000000000000000000000000000000000000001e848b", *lines.get(0).unwrap());
     }
+
+    #[test]
+    fn skips_chunks_of_other_shards() {
+        let file = "test/space.shard.txt";
+        std::fs::write(&file, "f4241-1e8481").unwrap();
+
+        let mut p = FileSearchSpaceProvider::with_shard(file, "2/2".parse().unwrap()).unwrap();
+        let s1 = p.next().search_space;
+        assert_eq!(0x2dc6c1, s1.start_inclusive.to_u64().unwrap());
+        assert_eq!(0x3d0901, s1.end_exclusive.to_u64().unwrap());
+
+        let s2 = p.next().search_space;
+        assert_eq!(0x4c4b41, s2.start_inclusive.to_u64().unwrap());
+        assert_eq!(0x5b8d81, s2.end_exclusive.to_u64().unwrap());
+
+        let mut p =
+            FileSearchSpaceProvider::with_shard(file, "1/2".parse::<Shard>().unwrap()).unwrap();
+        let s1 = p.next().search_space;
+        assert_eq!(0x1e8481, s1.start_inclusive.to_u64().unwrap());
+        assert_eq!(0x2dc6c1, s1.end_exclusive.to_u64().unwrap());
+    }
+
+    #[test]
+    fn reports_corrupted_line() {
+        let file = "test/space.corrupted.txt";
+        std::fs::write(&file, "4-b\n10-20\n30-x\n").unwrap();
+
+        let error = FileSearchSpaceProvider::new(file).err().unwrap();
+        assert_eq!(Some(3), error.line);
+        assert_eq!(
+            SearchSpaceError::InvalidNumber("x".to_string()),
+            error.error
+        );
+        assert_eq!(
+            "line 3: cannot parse search space '30-x': invalid hex number x",
+            error.to_string()
+        );
+
+        std::fs::write(&file, "b-4\n").unwrap();
+        let error = FileSearchSpaceProvider::new(file).err().unwrap();
+        assert_eq!(Some(1), error.line);
+        assert_eq!(SearchSpaceError::StartNotBelowEnd, error.error);
+    }
+
+    #[test]
+    fn skips_empty_search_space_and_starts_there() {
+        let mut p = FileSearchSpaceProvider::new("searchspace/done.txt").unwrap();
+        assert!(p.done.is_empty());
+
+        let search_space = p.next().search_space;
+        assert_eq!(BigUint::from(1u64) << 255, search_space.start_inclusive);
+    }
+
+    fn range_of(search_space: &SearchSpace) -> [u64; 2] {
+        [
+            search_space.start_inclusive.to_u64().unwrap(),
+            search_space.end_exclusive.to_u64().unwrap(),
+        ]
+    }
+
+    #[parameterized(direction = {
+        ScanDirection::Up, ScanDirection::Up, ScanDirection::Down, ScanDirection::Down, ScanDirection::Bidirectional,
+    }, fill_gaps = {
+        true, false, true, false, false,
+    }, expected = {
+        [[0x20, 0x30], [0x40, 0x50], [0x60, 0xf42a0]],
+        [[0x60, 0xf42a0], [0xf42a0, 0x1e84e0], [0x1e84e0, 0x2dc720]],
+        [[0x20, 0x30], [0x40, 0x50], [0x1, 0x10]],
+        [[0x1, 0x10], [0x60, 0xf42a0], [0xf42a0, 0x1e84e0]],
+        [[0x60, 0xf42a0], [0x1, 0x10], [0xf42a0, 0x1e84e0]],
+    })]
+    fn can_scan_with_options(direction: ScanDirection, fill_gaps: bool, expected: [[u64; 2]; 3]) {
+        let file = format!("test/space.scan.{:?}.{}.txt", direction, fill_gaps);
+        std::fs::write(&file, "10-20\n30-40\n50-60\n").unwrap();
+
+        let options = ScanOptions {
+            direction,
+            fill_gaps,
+            ..Default::default()
+        };
+        let mut p =
+            FileSearchSpaceProvider::with_options(&file, Shard::default(), options).unwrap();
+        for expected in expected {
+            assert_eq!(expected, range_of(&p.next().search_space));
+        }
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[parameterized(start = {
+        StartPolicy::Fixed(BigUint::from(0x100u64)),
+        StartPolicy::Fixed(BigUint::from(0x100u64)),
+        StartPolicy::RangeStart,
+    }, direction = {
+        ScanDirection::Up, ScanDirection::Down, ScanDirection::Up,
+    }, expected = {
+        [0x100, 0xf4340],
+        [0x1, 0x100],
+        [0x1, 0xf4241],
+    })]
+    fn can_start_with_policy(start: StartPolicy, direction: ScanDirection, expected: [u64; 2]) {
+        let options = ScanOptions {
+            start,
+            direction,
+            ..Default::default()
+        };
+        let mut p =
+            FileSearchSpaceProvider::with_options("test/space.none.txt", Shard::default(), options)
+                .unwrap();
+        assert_eq!(expected, range_of(&p.next().search_space));
+    }
+
+    #[test]
+    fn can_start_down_from_range_end() {
+        let options = ScanOptions {
+            start: StartPolicy::RangeStart,
+            direction: ScanDirection::Down,
+            ..Default::default()
+        };
+        let mut p =
+            FileSearchSpaceProvider::with_options("test/space.none.txt", Shard::default(), options)
+                .unwrap();
+        let search_space = p.next().search_space;
+        assert_eq!(
+            SearchSpace::key_space().end_exclusive,
+            search_space.end_exclusive
+        );
+        assert_eq!(
+            BigUint::from(1_000_000u64),
+            search_space.end_exclusive - search_space.start_inclusive
+        );
+    }
+
+    #[test]
+    fn can_parse_options() {
+        assert_eq!(StartPolicy::Random, "random".parse().unwrap());
+        assert_eq!(StartPolicy::RangeStart, "range-start".parse().unwrap());
+        assert_eq!(
+            StartPolicy::Fixed(BigUint::from(0xabu64)),
+            "ab".parse().unwrap()
+        );
+        assert!("x".parse::<StartPolicy>().is_err());
+        assert_eq!(ScanDirection::Bidirectional, "both".parse().unwrap());
+        assert!("left".parse::<ScanDirection>().is_err());
+    }
 }
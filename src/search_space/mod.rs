@@ -1,10 +1,17 @@
 pub mod file_search_space_provider;
+pub mod provider_spec;
 pub mod puzzle_search_space_provider;
 pub mod random_search_space_provider;
+pub mod range_search_space_provider;
+pub mod shard;
+pub mod weighted_search_space_provider;
 
+use hex_literal::hex;
 use num_bigint::BigUint;
-use num_traits::Num;
+use num_traits::{Num, One};
+use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
 pub struct SearchSpace {
@@ -12,53 +19,190 @@ pub struct SearchSpace {
     pub end_exclusive: BigUint,
 }
 
+/// A search space handed out by a provider, to be handed back to [`SearchSpaceProvider::done`]
+/// once it is searched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IssuedSearchSpace {
+    pub search_space: SearchSpace,
+    /// Indices of the children that issued the search space, innermost first, for providers that
+    /// combine others
+    pub route: Vec<usize>,
+}
+
+impl From<SearchSpace> for IssuedSearchSpace {
+    fn from(search_space: SearchSpace) -> Self {
+        IssuedSearchSpace {
+            search_space,
+            route: Vec::new(),
+        }
+    }
+}
+
 pub trait SearchSpaceProvider: Sync + Send {
-    fn next(&mut self) -> SearchSpace;
-    fn done(&mut self, search_space: &SearchSpace);
+    fn next(&mut self) -> IssuedSearchSpace;
+    fn done(&mut self, issued: &IssuedSearchSpace);
 }
 
 impl SearchSpace {
-    pub fn from_line(line: &str) -> SearchSpace {
-        let splitted = line.split("-").collect::<Vec<_>>();
-        if splitted.len() != 2 {
-            panic!("Line must be of format <number>-<number>");
+    /// All valid private keys, i.e. `1..n` with `n` being the order of secp256k1.
+    pub fn key_space() -> SearchSpace {
+        SearchSpace {
+            start_inclusive: BigUint::one(),
+            end_exclusive: BigUint::from_bytes_be(&hex!(
+                "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141"
+            )),
         }
+    }
 
-        let start_inclusive = BigUint::from_str_radix(splitted.get(0).unwrap(), 16).unwrap();
-        let end_exclusive = BigUint::from_str_radix(splitted.get(1).unwrap(), 16).unwrap();
+    /// Creates a search space, requiring `start_inclusive < end_exclusive <= n`.
+    pub fn new(
+        start_inclusive: BigUint,
+        end_exclusive: BigUint,
+    ) -> Result<SearchSpace, SearchSpaceError> {
+        if start_inclusive >= end_exclusive {
+            return Err(SearchSpaceError::StartNotBelowEnd);
+        }
+        if end_exclusive > Self::key_space().end_exclusive {
+            return Err(SearchSpaceError::EndBeyondKeySpace);
+        }
 
-        SearchSpace {
+        Ok(SearchSpace {
             start_inclusive,
             end_exclusive,
-        }
+        })
     }
 
     fn can_merge(&self, other: &SearchSpace) -> bool {
         let compared = self.start_inclusive.cmp(&other.start_inclusive);
         if compared.is_lt() {
-            return self.end_exclusive.cmp(&other.start_inclusive).is_ge();
+            self.end_exclusive.cmp(&other.start_inclusive).is_ge()
         } else if compared.is_eq() {
-            return true;
+            true
         } else {
-            return self.start_inclusive.cmp(&other.end_exclusive).is_le();
+            self.start_inclusive.cmp(&other.end_exclusive).is_le()
         }
     }
 
-    fn merge(&self, other: &SearchSpace) -> SearchSpace {
+    /// Merges two overlapping or adjacent search spaces.
+    pub fn try_merge(&self, other: &SearchSpace) -> Result<SearchSpace, SearchSpaceError> {
         if self.can_merge(other) {
-            SearchSpace {
+            Ok(SearchSpace {
                 start_inclusive: self
                     .start_inclusive
                     .clone()
                     .min(other.start_inclusive.clone()),
                 end_exclusive: self.end_exclusive.clone().max(other.end_exclusive.clone()),
-            }
+            })
         } else {
-            panic!("Cannot merge");
+            Err(SearchSpaceError::NotMergeable)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SearchSpaceError {
+    InvalidFormat,
+    InvalidNumber(String),
+    StartNotBelowEnd,
+    EndBeyondKeySpace,
+    NotMergeable,
+    /// The shard, e.g. `5/8`, owns none of the chunks of the range
+    EmptyShard(String),
+    /// The puzzle number is 0 or its keys exceed the order of secp256k1
+    InvalidPuzzle(usize),
+}
+
+impl Display for SearchSpaceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchSpaceError::InvalidFormat => write!(f, "must be of format <number>-<number>"),
+            SearchSpaceError::InvalidNumber(number) => write!(f, "invalid hex number {}", number),
+            SearchSpaceError::StartNotBelowEnd => write!(f, "start must be below end"),
+            SearchSpaceError::EndBeyondKeySpace => {
+                write!(f, "end must not exceed the order of secp256k1")
+            }
+            SearchSpaceError::NotMergeable => write!(f, "search spaces are disjoint"),
+            SearchSpaceError::EmptyShard(shard) => {
+                write!(f, "shard {} owns no chunk of the range", shard)
+            }
+            SearchSpaceError::InvalidPuzzle(number) => write!(
+                f,
+                "invalid puzzle number {}, expected 1 to {}",
+                number,
+                puzzle_search_space_provider::MAX_PUZZLE_NUMBER
+            ),
+        }
+    }
+}
+
+impl Error for SearchSpaceError {}
+
+/// A line that could not be parsed into a [`SearchSpace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseSearchSpaceError {
+    /// One-based line number, if the input was read from a file
+    pub line: Option<usize>,
+    pub input: String,
+    pub error: SearchSpaceError,
+}
+
+impl ParseSearchSpaceError {
+    pub fn at_line(self, line: usize) -> ParseSearchSpaceError {
+        ParseSearchSpaceError {
+            line: Some(line),
+            ..self
         }
     }
 }
 
+impl Display for ParseSearchSpaceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(
+            f,
+            "cannot parse search space '{}': {}",
+            self.input, self.error
+        )
+    }
+}
+
+impl Error for ParseSearchSpaceError {}
+
+impl FromStr for SearchSpace {
+    type Err = ParseSearchSpaceError;
+
+    /// Parses `<start>-<end>` with both numbers in hex.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_error = |error| ParseSearchSpaceError {
+            line: None,
+            input: s.to_string(),
+            error,
+        };
+        let parse_number = |number: &str| {
+            BigUint::from_str_radix(number, 16)
+                .map_err(|_| parse_error(SearchSpaceError::InvalidNumber(number.to_string())))
+        };
+
+        let (start_inclusive, end_exclusive) = s
+            .trim()
+            .split_once('-')
+            .ok_or_else(|| parse_error(SearchSpaceError::InvalidFormat))?;
+
+        SearchSpace::new(parse_number(start_inclusive)?, parse_number(end_exclusive)?)
+            .map_err(parse_error)
+    }
+}
+
+impl TryFrom<&str> for SearchSpace {
+    type Error = ParseSearchSpaceError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
 impl Display for SearchSpace {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -72,7 +216,7 @@ impl Display for SearchSpace {
 
 #[cfg(test)]
 mod test {
-    use crate::search_space::SearchSpace;
+    use crate::search_space::{SearchSpace, SearchSpaceError};
     use num_bigint::BigUint;
     use num_traits::ToPrimitive;
     use parameterized_macro::parameterized;
@@ -106,13 +250,41 @@ mod test {
         assert_eq!(can_merge, s2.can_merge(&s1));
 
         if can_merge {
-            let mut merged = s1.merge(&s2);
+            let mut merged = s1.try_merge(&s2).unwrap();
             assert_eq!(expected_merge[0], merged.start_inclusive.to_u64().unwrap());
             assert_eq!(expected_merge[1], merged.end_exclusive.to_u64().unwrap());
 
-            merged = s2.merge(&s1);
+            merged = s2.try_merge(&s1).unwrap();
             assert_eq!(expected_merge[0], merged.start_inclusive.to_u64().unwrap());
             assert_eq!(expected_merge[1], merged.end_exclusive.to_u64().unwrap());
+        } else {
+            assert_eq!(Err(SearchSpaceError::NotMergeable), s1.try_merge(&s2));
         }
     }
+
+    #[parameterized(line = {
+        "3-a", "0-1", " 4-b ",
+        "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140-fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+    })]
+    fn can_parse(line: &str) {
+        let search_space: SearchSpace = line.parse().unwrap();
+        assert_eq!(search_space, SearchSpace::try_from(line).unwrap());
+    }
+
+    #[parameterized(line = {
+        "3", "x-a", "3-", "a-3", "a-a",
+        "0-fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364142",
+    }, expected = {
+        SearchSpaceError::InvalidFormat,
+        SearchSpaceError::InvalidNumber("x".to_string()),
+        SearchSpaceError::InvalidNumber("".to_string()),
+        SearchSpaceError::StartNotBelowEnd,
+        SearchSpaceError::StartNotBelowEnd,
+        SearchSpaceError::EndBeyondKeySpace,
+    })]
+    fn rejects_invalid_line(line: &str, expected: SearchSpaceError) {
+        let error = line.parse::<SearchSpace>().unwrap_err();
+        assert_eq!(None, error.line);
+        assert_eq!(expected, error.error);
+    }
 }
@@ -0,0 +1,137 @@
+use crate::search_space::file_search_space_provider::{FileSearchSpaceProvider, ScanOptions};
+use crate::search_space::puzzle_search_space_provider;
+use crate::search_space::puzzle_search_space_provider::PuzzleSearchSpaceProvider;
+use crate::search_space::random_search_space_provider::RandomSearchSpaceProvider;
+use crate::search_space::range_search_space_provider::RangeSearchSpaceProvider;
+use crate::search_space::shard::Shard;
+use crate::search_space::{ParseSearchSpaceError, SearchSpace, SearchSpaceProvider};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Settings shared by all providers built from a [`ProviderSpec`].
+#[derive(Clone, Debug)]
+pub struct ProviderOptions {
+    pub shard: Shard,
+    /// Coverage file of the `file` provider
+    pub done_file: String,
+    /// Scan order of the `file` provider
+    pub scan: ScanOptions,
+}
+
+impl Default for ProviderOptions {
+    fn default() -> Self {
+        ProviderOptions {
+            shard: Shard::default(),
+            done_file: "searchspace/done.txt".to_string(),
+            scan: ScanOptions::default(),
+        }
+    }
+}
+
+/// Textual description of a provider: `file`, `random`, `puzzle:<number>` or `range:<start>-<end>`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProviderSpec {
+    File,
+    Random,
+    Puzzle(usize),
+    Range(SearchSpace),
+}
+
+impl ProviderSpec {
+    pub fn build(
+        &self,
+        options: &ProviderOptions,
+    ) -> Result<Box<dyn SearchSpaceProvider>, ParseSearchSpaceError> {
+        let shard = options.shard.clone();
+        let range_error = |range: String| {
+            move |error| ParseSearchSpaceError {
+                line: None,
+                input: range,
+                error,
+            }
+        };
+        Ok(match self {
+            ProviderSpec::File => Box::new(FileSearchSpaceProvider::with_options(
+                &options.done_file,
+                shard,
+                options.scan.clone(),
+            )?),
+            ProviderSpec::Random => Box::new(RandomSearchSpaceProvider::new()),
+            ProviderSpec::Puzzle(puzzle_number) => Box::new(
+                PuzzleSearchSpaceProvider::with_shard(*puzzle_number, shard)
+                    .map_err(range_error(self.to_string()))?,
+            ),
+            ProviderSpec::Range(range) => Box::new(
+                RangeSearchSpaceProvider::new(range.clone(), shard)
+                    .map_err(range_error(range.to_string()))?,
+            ),
+        })
+    }
+}
+
+impl FromStr for ProviderSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s, None),
+        };
+
+        match (name, argument) {
+            ("file", None) => Ok(ProviderSpec::File),
+            ("random", None) => Ok(ProviderSpec::Random),
+            ("puzzle", Some(number)) => {
+                puzzle_search_space_provider::parse_puzzle_number(number).map(ProviderSpec::Puzzle)
+            }
+            ("range", Some(range)) => range
+                .parse()
+                .map(ProviderSpec::Range)
+                .map_err(|e: ParseSearchSpaceError| e.to_string()),
+            _ => Err(format!(
+                "Unknown provider {}, expected file, random, puzzle:<number> or range:<start>-<end>",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ProviderSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderSpec::File => write!(f, "file"),
+            ProviderSpec::Random => write!(f, "random"),
+            ProviderSpec::Puzzle(puzzle_number) => write!(f, "puzzle:{}", puzzle_number),
+            ProviderSpec::Range(range) => write!(f, "range:{}", range),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+    use parameterized_macro::parameterized;
+
+    #[parameterized(spec = {"file", "random", "puzzle:69", "puzzle:128", "puzzle:255"})]
+    fn can_parse_and_display(spec: &str) {
+        assert_eq!(spec, spec.parse::<ProviderSpec>().unwrap().to_string());
+    }
+
+    #[test]
+    fn can_parse_range() {
+        let spec: ProviderSpec = "range:10-ff".parse().unwrap();
+        assert_eq!(
+            ProviderSpec::Range(SearchSpace {
+                start_inclusive: BigUint::from(0x10u64),
+                end_exclusive: BigUint::from(0xffu64),
+            }),
+            spec
+        );
+    }
+
+    #[parameterized(spec = {"puzzle", "puzzle:0", "puzzle:256", "puzzle:x", "range:10", "range:x-y", "range:ff-10", "file:x", "other"})]
+    fn rejects_invalid_spec(spec: &str) {
+        assert!(spec.parse::<ProviderSpec>().is_err());
+    }
+}
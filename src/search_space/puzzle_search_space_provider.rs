@@ -1,37 +1,86 @@
-use crate::search_space::{SearchSpace, SearchSpaceProvider};
-use num_bigint::{BigUint, RandBigInt};
-use num_traits::pow;
-use std::ops::Add;
+use crate::search_space::range_search_space_provider::RangeSearchSpaceProvider;
+use crate::search_space::shard::Shard;
+use crate::search_space::{IssuedSearchSpace, SearchSpace, SearchSpaceError, SearchSpaceProvider};
+use num_bigint::BigUint;
+use num_traits::One;
+
+/// The highest puzzle whose keys are all below the order of secp256k1.
+pub const MAX_PUZZLE_NUMBER: usize = 255;
 
 pub struct PuzzleSearchSpaceProvider {
-    lower_end: BigUint,
-    upper_end: BigUint,
+    range: RangeSearchSpaceProvider,
 }
 
 impl PuzzleSearchSpaceProvider {
-    pub fn new(puzzle_number: usize) -> PuzzleSearchSpaceProvider {
-        PuzzleSearchSpaceProvider {
-            lower_end: BigUint::from(pow(2u128, puzzle_number - 1)),
-            upper_end: BigUint::from(pow(2u128, puzzle_number)),
-        }
+    pub fn new(puzzle_number: usize) -> Result<PuzzleSearchSpaceProvider, SearchSpaceError> {
+        Self::with_shard(puzzle_number, Shard::default())
+    }
+
+    /// Fails if the shard owns no keys of the puzzle, as for small puzzles with many shards.
+    pub fn with_shard(
+        puzzle_number: usize,
+        shard: Shard,
+    ) -> Result<PuzzleSearchSpaceProvider, SearchSpaceError> {
+        Ok(PuzzleSearchSpaceProvider {
+            range: RangeSearchSpaceProvider::new(puzzle_range(puzzle_number)?, shard)?,
+        })
+    }
+}
+
+/// The keys of puzzle `n`, from 2^(n-1) inclusive to 2^n exclusive.
+pub fn puzzle_range(puzzle_number: usize) -> Result<SearchSpace, SearchSpaceError> {
+    if !(1..=MAX_PUZZLE_NUMBER).contains(&puzzle_number) {
+        return Err(SearchSpaceError::InvalidPuzzle(puzzle_number));
+    }
+    SearchSpace::new(
+        BigUint::one() << (puzzle_number - 1),
+        BigUint::one() << puzzle_number,
+    )
+}
+
+/// Parses a puzzle number between 1 and [`MAX_PUZZLE_NUMBER`].
+pub fn parse_puzzle_number(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(number) => puzzle_range(number)
+            .map(|_| number)
+            .map_err(|e| e.to_string()),
+        Err(_) => Err(format!("Invalid puzzle number {}", s)),
     }
 }
 
 impl SearchSpaceProvider for PuzzleSearchSpaceProvider {
-    fn next(&mut self) -> SearchSpace {
-        let mut rng = rand::thread_rng();
-        let start_inclusive: BigUint = rng.gen_biguint_range(&self.lower_end, &self.upper_end);
-        let number_of_keys: u64 = 0_800_000;
-        let number_of_keys = BigUint::from(number_of_keys);
-        let end_exclusive = start_inclusive.clone().add(&number_of_keys);
+    fn next(&mut self) -> IssuedSearchSpace {
+        self.range.next()
+    }
+
+    fn done(&mut self, issued: &IssuedSearchSpace) {
+        self.range.done(issued)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search_space::puzzle_search_space_provider::{puzzle_range, MAX_PUZZLE_NUMBER};
+    use crate::search_space::SearchSpaceError;
+    use num_bigint::BigUint;
+    use num_traits::One;
+    use parameterized_macro::parameterized;
 
-        SearchSpace {
-            start_inclusive,
-            end_exclusive,
-        }
+    #[parameterized(puzzle_number = {1, 69, 128, MAX_PUZZLE_NUMBER})]
+    fn covers_keys_of_bit_length(puzzle_number: usize) {
+        let range = puzzle_range(puzzle_number).unwrap();
+        assert_eq!(puzzle_number as u64, range.start_inclusive.bits());
+        assert_eq!(
+            puzzle_number as u64,
+            (range.end_exclusive - BigUint::one()).bits()
+        );
     }
 
-    fn done(&mut self, _search_space: &SearchSpace) {
-        //ignore
+    #[parameterized(puzzle_number = {0, MAX_PUZZLE_NUMBER + 1})]
+    fn rejects_puzzle_out_of_key_space(puzzle_number: usize) {
+        assert_eq!(
+            Err(SearchSpaceError::InvalidPuzzle(puzzle_number)),
+            puzzle_range(puzzle_number)
+        );
     }
 }
@@ -1,4 +1,4 @@
-use crate::search_space::{SearchSpace, SearchSpaceProvider};
+use crate::search_space::{IssuedSearchSpace, SearchSpace, SearchSpaceProvider};
 use num_bigint::{BigUint, RandBigInt};
 use std::ops::Add;
 
@@ -11,7 +11,7 @@ impl RandomSearchSpaceProvider {
 }
 
 impl SearchSpaceProvider for RandomSearchSpaceProvider {
-    fn next(&mut self) -> SearchSpace {
+    fn next(&mut self) -> IssuedSearchSpace {
         let mut rng = rand::thread_rng();
         let start_inclusive: BigUint = rng.gen_biguint(256);
         let number_of_keys: u64 = 0_800_000;
@@ -22,9 +22,10 @@ impl SearchSpaceProvider for RandomSearchSpaceProvider {
             start_inclusive,
             end_exclusive,
         }
+        .into()
     }
 
-    fn done(&mut self, _search_space: &SearchSpace) {
+    fn done(&mut self, _issued: &IssuedSearchSpace) {
         //ignore
     }
 }
@@ -0,0 +1,70 @@
+use crate::search_space::shard::{Shard, ShardedRange};
+use crate::search_space::{IssuedSearchSpace, SearchSpace, SearchSpaceError, SearchSpaceProvider};
+use num_bigint::{BigUint, RandBigInt};
+
+pub struct RangeSearchSpaceProvider {
+    range: ShardedRange,
+}
+
+impl RangeSearchSpaceProvider {
+    /// Fails if the shard owns no keys of the range.
+    pub fn new(
+        range: SearchSpace,
+        shard: Shard,
+    ) -> Result<RangeSearchSpaceProvider, SearchSpaceError> {
+        let number_of_keys: u64 = 800_000;
+        Ok(RangeSearchSpaceProvider {
+            range: ShardedRange::new(range, BigUint::from(number_of_keys), shard)?,
+        })
+    }
+}
+
+impl SearchSpaceProvider for RangeSearchSpaceProvider {
+    fn next(&mut self) -> IssuedSearchSpace {
+        let mut rng = rand::thread_rng();
+        let nth = rng.gen_biguint_below(&self.range.owned_chunk_count());
+        self.range.owned_chunk(&nth).into()
+    }
+
+    fn done(&mut self, _issued: &IssuedSearchSpace) {
+        //ignore
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_space::shard::ShardStrategy;
+    use num_traits::ToPrimitive;
+
+    #[test]
+    fn stays_within_shard() {
+        let mut provider = RangeSearchSpaceProvider::new(
+            SearchSpace {
+                start_inclusive: BigUint::from(0u64),
+                end_exclusive: BigUint::from(8_000_000u64),
+            },
+            Shard::new(2, 2, ShardStrategy::Contiguous),
+        )
+        .unwrap();
+
+        for _ in 0..20 {
+            let search_space = provider.next().search_space;
+            assert!(search_space.start_inclusive.to_u64().unwrap() >= 4_000_000);
+            assert!(search_space.end_exclusive.to_u64().unwrap() <= 8_000_000);
+        }
+    }
+
+    #[test]
+    fn rejects_shard_beyond_chunk_count() {
+        let range = SearchSpace {
+            start_inclusive: BigUint::from(0u64),
+            end_exclusive: BigUint::from(1_000_000u64),
+        };
+        let result = RangeSearchSpaceProvider::new(range, "3/4".parse().unwrap());
+        assert_eq!(
+            SearchSpaceError::EmptyShard("3/4".to_string()),
+            result.err().unwrap()
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use crate::search_space::{SearchSpace, SearchSpaceError};
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Div, Mul, Sub};
+use std::str::FromStr;
+
+/// How the chunks of a range are distributed over the shards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardStrategy {
+    /// Chunk `k` belongs to shard `k mod count`.
+    #[default]
+    Interleaved,
+    /// Every shard gets one contiguous slice of chunks.
+    Contiguous,
+}
+
+impl FromStr for ShardStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interleaved" => Ok(ShardStrategy::Interleaved),
+            "contiguous" => Ok(ShardStrategy::Contiguous),
+            _ => Err(format!(
+                "Unknown shard strategy {}, expected interleaved or contiguous",
+                s
+            )),
+        }
+    }
+}
+
+/// One of `count` disjoint shares of a range. Parsed from `<index>/<count>` with `index` starting at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shard {
+    index: usize,
+    count: usize,
+    strategy: ShardStrategy,
+}
+
+impl Shard {
+    pub fn new(index: usize, count: usize, strategy: ShardStrategy) -> Shard {
+        if count == 0 || index == 0 || index > count {
+            panic!("Shard {}/{} is out of range", index, count);
+        }
+
+        Shard {
+            index: index - 1,
+            count,
+            strategy,
+        }
+    }
+
+    pub fn with_strategy(self, strategy: ShardStrategy) -> Shard {
+        Shard { strategy, ..self }
+    }
+
+    pub fn is_sharded(&self) -> bool {
+        self.count > 1
+    }
+
+    fn to_fraction(&self) -> String {
+        format!("{}/{}", self.index + 1, self.count)
+    }
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Shard::new(1, 1, ShardStrategy::default())
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| format!("Shard must be of format <index>/<count>, got {}", s))?;
+        let index: usize = index
+            .parse()
+            .map_err(|_| format!("Invalid shard index {}", index))?;
+        let count: usize = count
+            .parse()
+            .map_err(|_| format!("Invalid shard count {}", count))?;
+        if count == 0 || index == 0 || index > count {
+            return Err(format!("Shard index must be within 1..={}", count));
+        }
+
+        Ok(Shard::new(index, count, ShardStrategy::default()))
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({:?})", self.to_fraction(), self.strategy)
+    }
+}
+
+/// A range split into chunks of `chunk_size`, of which only the chunks owned by `shard` are handed out.
+#[derive(Debug)]
+pub struct ShardedRange {
+    range: SearchSpace,
+    chunk_size: BigUint,
+    chunk_count: BigUint,
+    shard: Shard,
+}
+
+impl ShardedRange {
+    /// Fails if the shard owns none of the chunks, e.g. with more shards than chunks.
+    pub fn new(
+        range: SearchSpace,
+        chunk_size: BigUint,
+        shard: Shard,
+    ) -> Result<ShardedRange, SearchSpaceError> {
+        let length = range.end_exclusive.clone().sub(&range.start_inclusive);
+        let chunk_count = length.add(&chunk_size).sub(BigUint::one()).div(&chunk_size);
+
+        let sharded = ShardedRange {
+            range,
+            chunk_size,
+            chunk_count,
+            shard,
+        };
+        if sharded.owned_chunk_count().is_zero() {
+            return Err(SearchSpaceError::EmptyShard(sharded.shard.to_fraction()));
+        }
+        Ok(sharded)
+    }
+
+    pub fn range(&self) -> &SearchSpace {
+        &self.range
+    }
+
+    pub fn shard(&self) -> &Shard {
+        &self.shard
+    }
+
+    /// Number of chunks owned by the shard.
+    pub fn owned_chunk_count(&self) -> BigUint {
+        let (lower, upper) = self.contiguous_bounds();
+        match self.shard.strategy {
+            ShardStrategy::Contiguous => upper.sub(lower),
+            ShardStrategy::Interleaved => {
+                let index = BigUint::from(self.shard.index);
+                if self.chunk_count <= index {
+                    BigUint::zero()
+                } else {
+                    self.chunk_count
+                        .clone()
+                        .sub(&index)
+                        .sub(BigUint::one())
+                        .div(self.shard.count)
+                        .add(BigUint::one())
+                }
+            }
+        }
+    }
+
+    /// The `nth` chunk owned by the shard, `nth` must be below [`Self::owned_chunk_count`].
+    pub fn owned_chunk(&self, nth: &BigUint) -> SearchSpace {
+        let index = match self.shard.strategy {
+            ShardStrategy::Contiguous => self.contiguous_bounds().0.add(nth),
+            ShardStrategy::Interleaved => nth.mul(self.shard.count).add(self.shard.index),
+        };
+        self.chunk(&index)
+    }
+
+    /// The next owned keys at or after `position`, at most `chunk_size` keys and never crossing into
+    /// keys owned by another shard. Returns `None` if the shard owns no keys at or after `position`.
+    pub fn next_owned(&self, position: &BigUint) -> Option<SearchSpace> {
+        if position >= &self.range.end_exclusive {
+            return None;
+        }
+        let position = position.max(&self.range.start_inclusive).clone();
+
+        let index = self.chunk_index(&position);
+        if self.owns(&index) {
+            let end_exclusive = position
+                .clone()
+                .add(&self.chunk_size)
+                .min(self.owned_run_end(&index));
+            return Some(SearchSpace {
+                start_inclusive: position,
+                end_exclusive,
+            });
+        }
+
+        let next_index = match self.shard.strategy {
+            ShardStrategy::Contiguous => {
+                let (lower, upper) = self.contiguous_bounds();
+                if index < lower && lower < upper {
+                    lower
+                } else {
+                    return None;
+                }
+            }
+            ShardStrategy::Interleaved => {
+                let count = BigUint::from(self.shard.count);
+                let shard_index = BigUint::from(self.shard.index);
+                let offset = shard_index.add(&count).sub(&index % &count) % &count;
+                index.add(offset)
+            }
+        };
+
+        if next_index < self.chunk_count {
+            Some(self.chunk(&next_index))
+        } else {
+            None
+        }
+    }
+
+    /// The last owned keys below `position`, at most `chunk_size` keys and never crossing into
+    /// keys owned by another shard. Returns `None` if the shard owns no keys below `position`.
+    pub fn prev_owned(&self, position: &BigUint) -> Option<SearchSpace> {
+        if position <= &self.range.start_inclusive {
+            return None;
+        }
+        let position = position.min(&self.range.end_exclusive).clone();
+
+        let index = self.chunk_index(&position.clone().sub(BigUint::one()));
+        if self.owns(&index) {
+            let run_start = self.owned_run_start(&index);
+            let start_inclusive = if position > run_start.clone().add(&self.chunk_size) {
+                position.clone().sub(&self.chunk_size)
+            } else {
+                run_start
+            };
+            return Some(SearchSpace {
+                start_inclusive,
+                end_exclusive: position,
+            });
+        }
+
+        let prev_index = match self.shard.strategy {
+            ShardStrategy::Contiguous => {
+                let (lower, upper) = self.contiguous_bounds();
+                if index >= upper && lower < upper {
+                    upper.sub(BigUint::one())
+                } else {
+                    return None;
+                }
+            }
+            ShardStrategy::Interleaved => {
+                let shard_index = BigUint::from(self.shard.index);
+                if index < shard_index {
+                    return None;
+                }
+                let offset = index.clone().sub(&shard_index) % self.shard.count;
+                index.sub(offset)
+            }
+        };
+
+        Some(self.chunk(&prev_index))
+    }
+
+    fn owns(&self, index: &BigUint) -> bool {
+        match self.shard.strategy {
+            ShardStrategy::Contiguous => {
+                let (lower, upper) = self.contiguous_bounds();
+                &lower <= index && index < &upper
+            }
+            ShardStrategy::Interleaved => {
+                index % self.shard.count == BigUint::from(self.shard.index)
+            }
+        }
+    }
+
+    /// End of the block of consecutive owned keys that contains chunk `index`.
+    fn owned_run_end(&self, index: &BigUint) -> BigUint {
+        let run_end_index = match self.shard.strategy {
+            ShardStrategy::Contiguous => self.contiguous_bounds().1,
+            ShardStrategy::Interleaved if !self.shard.is_sharded() => self.chunk_count.clone(),
+            ShardStrategy::Interleaved => index.add(BigUint::one()),
+        };
+        self.chunk_start(&run_end_index)
+            .min(self.range.end_exclusive.clone())
+    }
+
+    /// Start of the block of consecutive owned keys that contains chunk `index`.
+    fn owned_run_start(&self, index: &BigUint) -> BigUint {
+        match self.shard.strategy {
+            ShardStrategy::Contiguous => self.chunk_start(&self.contiguous_bounds().0),
+            ShardStrategy::Interleaved if !self.shard.is_sharded() => {
+                self.range.start_inclusive.clone()
+            }
+            ShardStrategy::Interleaved => self.chunk_start(index),
+        }
+    }
+
+    fn contiguous_bounds(&self) -> (BigUint, BigUint) {
+        let lower = (&self.chunk_count)
+            .mul(self.shard.index)
+            .div(self.shard.count);
+        let upper = (&self.chunk_count)
+            .mul(self.shard.index + 1)
+            .div(self.shard.count);
+        (lower, upper)
+    }
+
+    fn chunk_index(&self, position: &BigUint) -> BigUint {
+        position
+            .clone()
+            .sub(&self.range.start_inclusive)
+            .div(&self.chunk_size)
+    }
+
+    fn chunk_start(&self, index: &BigUint) -> BigUint {
+        index.mul(&self.chunk_size).add(&self.range.start_inclusive)
+    }
+
+    fn chunk(&self, index: &BigUint) -> SearchSpace {
+        let start_inclusive = self.chunk_start(index);
+        let end_exclusive = start_inclusive
+            .clone()
+            .add(&self.chunk_size)
+            .min(self.range.end_exclusive.clone());
+        SearchSpace {
+            start_inclusive,
+            end_exclusive,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::ToPrimitive;
+    use parameterized_macro::parameterized;
+
+    fn collect_owned(range: &ShardedRange) -> Vec<SearchSpace> {
+        let mut owned = Vec::new();
+        let mut position = range.range().start_inclusive.clone();
+        while let Some(search_space) = range.next_owned(&position) {
+            position = search_space.end_exclusive.clone();
+            owned.push(search_space);
+        }
+        owned
+    }
+
+    fn collect_owned_downwards(range: &ShardedRange) -> Vec<SearchSpace> {
+        let mut owned = Vec::new();
+        let mut position = range.range().end_exclusive.clone();
+        while let Some(search_space) = range.prev_owned(&position) {
+            position = search_space.start_inclusive.clone();
+            owned.push(search_space);
+        }
+        owned.reverse();
+        owned
+    }
+
+    fn mark_covered(covered: &mut [u8], offset: u64, search_spaces: &[SearchSpace]) {
+        for search_space in search_spaces {
+            let start = search_space.start_inclusive.to_u64().unwrap() - offset;
+            let end = search_space.end_exclusive.to_u64().unwrap() - offset;
+            for key in start..end {
+                covered[key as usize] += 1;
+            }
+        }
+    }
+
+    #[parameterized( range = {
+        [0, 100], [5, 1005], [3, 4], [7, 57], [1, 1000], [0, 20],
+    }, chunk_size = {
+        10, 7, 10, 3, 1000, 10,
+    }, count = {
+        8, 3, 2, 5, 4, 4,
+    })]
+    fn shards_cover_range_without_overlap(range: [u64; 2], chunk_size: u64, count: usize) {
+        for strategy in [ShardStrategy::Interleaved, ShardStrategy::Contiguous] {
+            let mut covered = vec![0u8; (range[1] - range[0]) as usize];
+            let mut covered_downwards = covered.clone();
+            for index in 1..=count {
+                let sharded = ShardedRange::new(
+                    SearchSpace {
+                        start_inclusive: BigUint::from(range[0]),
+                        end_exclusive: BigUint::from(range[1]),
+                    },
+                    BigUint::from(chunk_size),
+                    Shard::new(index, count, strategy),
+                );
+                // shards beyond the chunk count own nothing and cannot be created
+                let sharded = match sharded {
+                    Ok(sharded) => sharded,
+                    Err(SearchSpaceError::EmptyShard(_)) => continue,
+                    Err(e) => panic!("{}", e),
+                };
+
+                let owned = collect_owned(&sharded);
+                let owned_by_nth: Vec<SearchSpace> =
+                    (0..sharded.owned_chunk_count().to_u64().unwrap())
+                        .map(|nth| sharded.owned_chunk(&BigUint::from(nth)))
+                        .collect();
+                assert_eq!(owned, owned_by_nth);
+
+                mark_covered(&mut covered, range[0], &owned);
+                mark_covered(
+                    &mut covered_downwards,
+                    range[0],
+                    &collect_owned_downwards(&sharded),
+                );
+            }
+            assert!(covered.iter().all(|c| *c == 1), "{:?}", strategy);
+            assert!(covered_downwards.iter().all(|c| *c == 1), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn next_owned_skips_foreign_chunks() {
+        let sharded = ShardedRange::new(
+            SearchSpace {
+                start_inclusive: BigUint::from(0u64),
+                end_exclusive: BigUint::from(100u64),
+            },
+            BigUint::from(10u64),
+            Shard::new(2, 4, ShardStrategy::Interleaved),
+        )
+        .unwrap();
+
+        let next = sharded.next_owned(&BigUint::from(15u64)).unwrap();
+        assert_eq!(15, next.start_inclusive.to_u64().unwrap());
+        assert_eq!(20, next.end_exclusive.to_u64().unwrap());
+
+        let next = sharded.next_owned(&BigUint::from(20u64)).unwrap();
+        assert_eq!(50, next.start_inclusive.to_u64().unwrap());
+        assert_eq!(60, next.end_exclusive.to_u64().unwrap());
+
+        let next = sharded.next_owned(&BigUint::from(60u64)).unwrap();
+        assert_eq!(90, next.start_inclusive.to_u64().unwrap());
+        assert_eq!(100, next.end_exclusive.to_u64().unwrap());
+
+        assert!(sharded.next_owned(&BigUint::from(100u64)).is_none());
+    }
+
+    #[test]
+    fn prev_owned_skips_foreign_chunks() {
+        let sharded = ShardedRange::new(
+            SearchSpace {
+                start_inclusive: BigUint::from(0u64),
+                end_exclusive: BigUint::from(100u64),
+            },
+            BigUint::from(10u64),
+            Shard::new(2, 4, ShardStrategy::Interleaved),
+        )
+        .unwrap();
+
+        let prev = sharded.prev_owned(&BigUint::from(55u64)).unwrap();
+        assert_eq!(50, prev.start_inclusive.to_u64().unwrap());
+        assert_eq!(55, prev.end_exclusive.to_u64().unwrap());
+
+        let prev = sharded.prev_owned(&BigUint::from(50u64)).unwrap();
+        assert_eq!(10, prev.start_inclusive.to_u64().unwrap());
+        assert_eq!(20, prev.end_exclusive.to_u64().unwrap());
+
+        assert!(sharded.prev_owned(&BigUint::from(10u64)).is_none());
+    }
+
+    #[test]
+    fn unsharded_range_is_not_aligned() {
+        let sharded = ShardedRange::new(
+            SearchSpace {
+                start_inclusive: BigUint::from(0u64),
+                end_exclusive: BigUint::from(100u64),
+            },
+            BigUint::from(10u64),
+            Shard::default(),
+        )
+        .unwrap();
+
+        let next = sharded.next_owned(&BigUint::from(15u64)).unwrap();
+        assert_eq!(15, next.start_inclusive.to_u64().unwrap());
+        assert_eq!(25, next.end_exclusive.to_u64().unwrap());
+
+        let prev = sharded.prev_owned(&BigUint::from(15u64)).unwrap();
+        assert_eq!(5, prev.start_inclusive.to_u64().unwrap());
+        assert_eq!(15, prev.end_exclusive.to_u64().unwrap());
+    }
+
+    #[parameterized(shard = {"2/2", "4/4", "1/2"}, strategy = {
+        ShardStrategy::Interleaved, ShardStrategy::Interleaved, ShardStrategy::Contiguous,
+    })]
+    fn rejects_shard_without_chunks(shard: &str, strategy: ShardStrategy) {
+        let shard = shard.parse::<Shard>().unwrap().with_strategy(strategy);
+        let sharded = ShardedRange::new(
+            SearchSpace {
+                start_inclusive: BigUint::from(0u64),
+                end_exclusive: BigUint::from(5u64),
+            },
+            BigUint::from(10u64),
+            shard.clone(),
+        );
+        assert_eq!(
+            SearchSpaceError::EmptyShard(shard.to_fraction()),
+            sharded.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn can_parse_shard() {
+        assert_eq!(
+            Shard::new(3, 8, ShardStrategy::Interleaved),
+            "3/8".parse().unwrap()
+        );
+        assert!("0/8".parse::<Shard>().is_err());
+        assert!("9/8".parse::<Shard>().is_err());
+        assert!("3".parse::<Shard>().is_err());
+        assert_eq!(
+            ShardStrategy::Contiguous,
+            "contiguous".parse::<ShardStrategy>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::search_space::provider_spec::{ProviderOptions, ProviderSpec};
+use crate::search_space::{IssuedSearchSpace, ParseSearchSpaceError, SearchSpaceProvider};
+use log::{debug, error};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::str::FromStr;
+
+/// A child provider with its share of the work, parsed from `<weight>=<provider>`, e.g. `70=puzzle:69`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedSpec {
+    pub weight: u32,
+    pub provider: ProviderSpec,
+}
+
+impl FromStr for WeightedSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (weight, provider) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Mix must be of format <weight>=<provider>, got {}", s))?;
+        let weight: u32 = weight
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid weight {}", weight))?;
+        if weight == 0 {
+            return Err(format!("Weight of {} must be positive", provider));
+        }
+
+        Ok(WeightedSpec {
+            weight,
+            provider: provider.trim().parse()?,
+        })
+    }
+}
+
+impl Display for WeightedSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.weight, self.provider)
+    }
+}
+
+/// Reads one `<weight>=<provider>` per line, empty lines and lines starting with `#` are ignored.
+pub fn read_mix_file(file: &str) -> Result<Vec<WeightedSpec>, String> {
+    let content =
+        fs::read_to_string(file).map_err(|e| format!("Cannot read mix file {}: {}", file, e))?;
+
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            line.parse()
+                .map_err(|e| format!("{}, line {}: {}", file, i + 1, e))
+        })
+        .collect()
+}
+
+struct WeightedChild {
+    provider: Box<dyn SearchSpaceProvider>,
+    weight: i64,
+    current_weight: i64,
+}
+
+/// Interleaves several providers by smooth weighted round-robin. Completed search spaces are
+/// handed back to the provider that issued them.
+pub struct WeightedSearchSpaceProvider {
+    children: Vec<WeightedChild>,
+}
+
+impl WeightedSearchSpaceProvider {
+    pub fn new(children: Vec<(Box<dyn SearchSpaceProvider>, u32)>) -> WeightedSearchSpaceProvider {
+        if children.is_empty() {
+            panic!("At least one provider is required");
+        }
+
+        WeightedSearchSpaceProvider {
+            children: children
+                .into_iter()
+                .map(|(provider, weight)| WeightedChild {
+                    provider,
+                    weight: weight as i64,
+                    current_weight: 0,
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds the children of the specs, of which at most one may be `file`, as all of them would
+    /// record their coverage in the same done file.
+    pub fn from_specs(
+        specs: &[WeightedSpec],
+        options: &ProviderOptions,
+    ) -> Result<WeightedSearchSpaceProvider, String> {
+        let file_children = specs
+            .iter()
+            .filter(|spec| spec.provider == ProviderSpec::File)
+            .count();
+        if file_children > 1 {
+            return Err(format!(
+                "Mix has {} file providers, but they would share {}",
+                file_children, options.done_file
+            ));
+        }
+
+        let children = specs
+            .iter()
+            .map(|spec| Ok((spec.provider.build(options)?, spec.weight)))
+            .collect::<Result<_, ParseSearchSpaceError>>()
+            .map_err(|e| e.to_string())?;
+        Ok(Self::new(children))
+    }
+
+    fn pick(&mut self) -> usize {
+        let total: i64 = self.children.iter().map(|c| c.weight).sum();
+        for child in self.children.iter_mut() {
+            child.current_weight += child.weight;
+        }
+
+        let (picked, _) = self
+            .children
+            .iter()
+            .enumerate()
+            .max_by(|(i, a), (j, b)| a.current_weight.cmp(&b.current_weight).then(j.cmp(i)))
+            .unwrap();
+        self.children[picked].current_weight -= total;
+        picked
+    }
+}
+
+impl SearchSpaceProvider for WeightedSearchSpaceProvider {
+    fn next(&mut self) -> IssuedSearchSpace {
+        let picked = self.pick();
+        let issued = self.children[picked].provider.next();
+        debug!("Provider #{} issued {}", picked, issued.search_space);
+
+        IssuedSearchSpace {
+            route: issued.route.into_iter().chain([picked]).collect(),
+            ..issued
+        }
+    }
+
+    fn done(&mut self, issued: &IssuedSearchSpace) {
+        let mut route = issued.route.clone();
+        match route.pop().and_then(|child| self.children.get_mut(child)) {
+            Some(child) => child.provider.done(&IssuedSearchSpace {
+                search_space: issued.search_space.clone(),
+                route,
+            }),
+            None => error!(
+                "Search space {} was not issued by a provider of the mix",
+                issued.search_space
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_space::SearchSpace;
+    use num_bigint::BigUint;
+    use std::sync::{Arc, Mutex};
+
+    struct CountingProvider {
+        id: u64,
+        issued: u64,
+        done: Arc<Mutex<Vec<SearchSpace>>>,
+    }
+
+    impl SearchSpaceProvider for CountingProvider {
+        fn next(&mut self) -> IssuedSearchSpace {
+            self.issued += 1;
+            SearchSpace {
+                start_inclusive: BigUint::from(self.id * 1000 + self.issued),
+                end_exclusive: BigUint::from(self.id * 1000 + self.issued + 1),
+            }
+            .into()
+        }
+
+        fn done(&mut self, issued: &IssuedSearchSpace) {
+            assert!(issued.route.is_empty());
+            self.done.lock().unwrap().push(issued.search_space.clone());
+        }
+    }
+
+    fn counting_provider(id: u64) -> (Box<dyn SearchSpaceProvider>, Arc<Mutex<Vec<SearchSpace>>>) {
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let provider = CountingProvider {
+            id,
+            issued: 0,
+            done: done.clone(),
+        };
+        (Box::new(provider), done)
+    }
+
+    #[test]
+    fn interleaves_by_weight_and_routes_completions() {
+        let (puzzle, puzzle_done) = counting_provider(1);
+        let (random, random_done) = counting_provider(2);
+        let mut provider = WeightedSearchSpaceProvider::new(vec![(puzzle, 7), (random, 3)]);
+
+        let issued: Vec<IssuedSearchSpace> = (0..100).map(|_| provider.next()).collect();
+        for issued in issued.iter().rev() {
+            provider.done(issued);
+        }
+
+        let puzzle_done = puzzle_done.lock().unwrap();
+        let random_done = random_done.lock().unwrap();
+        assert_eq!(70, puzzle_done.len());
+        assert_eq!(30, random_done.len());
+        assert!(puzzle_done
+            .iter()
+            .all(|s| s.start_inclusive < BigUint::from(2000u64)));
+        assert!(random_done
+            .iter()
+            .all(|s| s.start_inclusive > BigUint::from(2000u64)));
+
+        // smooth round-robin never issues more than 3 chunks of the heavier provider in a row
+        let longest_run = issued
+            .windows(4)
+            .filter(|w| {
+                w.iter()
+                    .all(|s| s.search_space.start_inclusive < BigUint::from(2000u64))
+            })
+            .count();
+        assert_eq!(0, longest_run);
+    }
+
+    #[test]
+    fn routes_identical_search_spaces_to_their_child() {
+        let (first, first_done) = counting_provider(1);
+        let (second, second_done) = counting_provider(1);
+        let mut provider = WeightedSearchSpaceProvider::new(vec![(first, 1), (second, 1)]);
+
+        let issued_by_first = provider.next();
+        let issued_by_second = provider.next();
+        assert_eq!(issued_by_first.search_space, issued_by_second.search_space);
+
+        provider.done(&issued_by_second);
+        assert!(first_done.lock().unwrap().is_empty());
+        assert_eq!(1, second_done.lock().unwrap().len());
+
+        // not issued by the mix, ignored
+        provider.done(&issued_by_second.search_space.clone().into());
+        assert_eq!(1, second_done.lock().unwrap().len());
+    }
+
+    #[test]
+    fn rejects_several_file_providers() {
+        let specs: Vec<WeightedSpec> = vec!["50=file".parse().unwrap(), "50=file".parse().unwrap()];
+        let result = WeightedSearchSpaceProvider::from_specs(&specs, &ProviderOptions::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn can_parse_weighted_spec() {
+        let spec: WeightedSpec = "70=puzzle:69".parse().unwrap();
+        assert_eq!(70, spec.weight);
+        assert_eq!(ProviderSpec::Puzzle(69), spec.provider);
+        assert_eq!("70=puzzle:69", spec.to_string());
+
+        assert!("0=random".parse::<WeightedSpec>().is_err());
+        assert!("random".parse::<WeightedSpec>().is_err());
+        assert!("30=unknown".parse::<WeightedSpec>().is_err());
+    }
+}
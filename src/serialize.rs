@@ -0,0 +1,91 @@
+use std::io;
+use std::io::Read;
+
+/// Reads a fixed number of bytes.
+pub fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn read_vec(reader: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    read_bytes(reader).map(u16::from_le_bytes)
+}
+
+pub fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    read_bytes(reader).map(u32::from_le_bytes)
+}
+
+pub fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    read_bytes(reader).map(u64::from_le_bytes)
+}
+
+/// Reads a CompactSize, the length prefix of vectors in transactions and blocks.
+pub fn read_compact_size(reader: &mut impl Read) -> io::Result<u64> {
+    let [first] = read_bytes(reader)?;
+    match first {
+        0xfd => read_u16(reader).map(u64::from),
+        0xfe => read_u32(reader).map(u64::from),
+        0xff => read_u64(reader),
+        _ => Ok(u64::from(first)),
+    }
+}
+
+/// Reads a VARINT of Bitcoin Core's storage format: base 128 with the most significant digit
+/// first, every continued digit offset by one.
+pub fn read_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut n: u64 = 0;
+    loop {
+        let [byte] = read_bytes(reader)?;
+        if n > (u64::MAX >> 7) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "VARINT too large",
+            ));
+        }
+        n = (n << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        n = n
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "VARINT too large"))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialize::{read_compact_size, read_varint};
+    use hex_literal::hex;
+    use parameterized_macro::parameterized;
+
+    #[parameterized(bytes = {
+    &hex!("00"), &hex!("7f"), &hex!("8000"), &hex!("a334"), &hex!("82fe7f"), &hex!("8efefefe7f"),
+    }, expected = {
+    0, 0x7f, 0x80, 0x1234, 0xffff, 0xffffffff,
+    })]
+    fn can_read_varint(bytes: &[u8], expected: u64) {
+        assert_eq!(expected, read_varint(&mut &bytes[..]).unwrap());
+    }
+
+    #[parameterized(bytes = {
+    &hex!("fc"), &hex!("fdfd00"), &hex!("fe00000100"), &hex!("ff0000000001000000"),
+    }, expected = {
+    0xfc, 0xfd, 0x10000, 0x100000000,
+    })]
+    fn can_read_compact_size(bytes: &[u8], expected: u64) {
+        assert_eq!(expected, read_compact_size(&mut &bytes[..]).unwrap());
+    }
+
+    #[test]
+    fn fails_on_truncated_input() {
+        assert!(read_varint(&mut &hex!("80")[..]).is_err());
+        assert!(read_compact_size(&mut &hex!("fd00")[..]).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::Utc;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// A named run with its own state directory holding the search space coverage, a snapshot of the
+/// command line, the found collisions and the logs.
+pub struct Session {
+    name: String,
+    dir: PathBuf,
+}
+
+impl Session {
+    const ARCHIVE_DIR: &'static str = "archive";
+    const DONE_FILE: &'static str = "done.txt";
+    const CONFIG_FILE: &'static str = "config.txt";
+    const RESULTS_FILE: &'static str = "collisions.txt";
+    const LOG_DIR: &'static str = "log";
+
+    /// Opens the session `name` below `root`, creating it if it does not exist yet.
+    pub fn open(root: &str, name: &str) -> io::Result<Session> {
+        let session = Self::at(root, name)?;
+        fs::create_dir_all(session.dir.join(Self::LOG_DIR))?;
+        Ok(session)
+    }
+
+    /// Opens the existing session `name` below `root`.
+    pub fn existing(root: &str, name: &str) -> io::Result<Session> {
+        let session = Self::at(root, name)?;
+        if !session.dir.is_dir() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("Session {} does not exist", name),
+            ));
+        }
+        Ok(session)
+    }
+
+    fn at(root: &str, name: &str) -> io::Result<Session> {
+        let valid = !name.is_empty()
+            && name != Self::ARCHIVE_DIR
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+        if !valid {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid session name {}", name),
+            ));
+        }
+
+        Ok(Session {
+            name: name.to_string(),
+            dir: Path::new(root).join(name),
+        })
+    }
+
+    /// Names of all sessions below `root` that are not archived.
+    pub fn list(root: &str) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() && name != Self::ARCHIVE_DIR {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Moves the session into the archive directory of `root`, returning its new location.
+    pub fn archive(self, root: &str) -> io::Result<PathBuf> {
+        let archive_dir = Path::new(root).join(Self::ARCHIVE_DIR);
+        fs::create_dir_all(&archive_dir)?;
+
+        let target = archive_dir.join(format!(
+            "{}-{}",
+            self.name,
+            Utc::now().format("%Y-%m-%dT%H%M%S")
+        ));
+        fs::rename(&self.dir, &target)?;
+        Ok(target)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn done_file(&self) -> String {
+        self.path(Self::DONE_FILE)
+    }
+
+    pub fn results_file(&self) -> String {
+        self.path(Self::RESULTS_FILE)
+    }
+
+    pub fn log_dir(&self) -> String {
+        self.path(Self::LOG_DIR)
+    }
+
+    /// Stores the command line arguments the session was started with, one per line.
+    pub fn write_config(&self, args: &[String]) -> io::Result<()> {
+        let mut content = args.join("\n");
+        content.push('\n');
+        fs::write(self.path(Self::CONFIG_FILE), content)
+    }
+
+    /// The command line arguments the session was started with.
+    pub fn read_config(&self) -> io::Result<Vec<String>> {
+        let content = fs::read_to_string(self.path(Self::CONFIG_FILE))?;
+        Ok(content.lines().map(|line| line.to_string()).collect())
+    }
+
+    pub fn append_result(&self, line: &str) -> io::Result<()> {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.results_file())?;
+        writeln!(f, "{}", line)
+    }
+
+    fn path(&self, file: &str) -> String {
+        self.dir.join(file).to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use parameterized_macro::parameterized;
+
+    fn test_root(test: &str) -> String {
+        temp_dir(&format!("sessions-{}", test))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn can_list_resume_and_archive() {
+        let root = test_root("lifecycle");
+        assert!(Session::list(&root).unwrap().is_empty());
+
+        let session = Session::open(&root, "puzzle-69").unwrap();
+        session
+            .write_config(&["--puzzle".to_string(), "69".to_string()])
+            .unwrap();
+        session.append_result("first").unwrap();
+        session.append_result("second").unwrap();
+        Session::open(&root, "random").unwrap();
+        assert_eq!(vec!["puzzle-69", "random"], Session::list(&root).unwrap());
+
+        let resumed = Session::existing(&root, "puzzle-69").unwrap();
+        assert_eq!(vec!["--puzzle", "69"], resumed.read_config().unwrap());
+        assert_eq!(
+            "first\nsecond\n",
+            fs::read_to_string(resumed.results_file()).unwrap()
+        );
+
+        let archived = resumed.archive(&root).unwrap();
+        assert!(archived.join("config.txt").is_file());
+        assert_eq!(vec!["random"], Session::list(&root).unwrap());
+        assert!(Session::existing(&root, "puzzle-69").is_err());
+    }
+
+    #[parameterized(name = {"", "archive", "..", "a/b", ".hidden"})]
+    fn rejects_invalid_name(name: &str) {
+        assert!(Session::open(&test_root("invalid"), name).is_err());
+    }
+}
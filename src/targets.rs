@@ -0,0 +1,555 @@
+use crate::btc_address;
+use crate::btc_address::{AddressTypes, BTCAddressType};
+use crate::error::Error;
+use crate::hash_util;
+use crate::network::Network;
+use primitive_types::{H160, H256};
+use secp256k1::PublicKey;
+use std::collections::{HashMap, HashSet};
+
+/// What the collider matches a target by.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    PubkeyHash(H160),
+    ScriptHash(H160),
+    WitnessScriptHash(H256),
+    OutputKey(H256),
+    /// Public key of a P2PK output, or of a hash address that revealed it, and whether it is
+    /// compressed
+    PublicKey(PublicKey, bool),
+}
+
+impl Target {
+    /// The type the target is listed as unless its info says otherwise, P2PKH for a public key hash.
+    pub fn address_type(&self) -> BTCAddressType {
+        match self {
+            Target::PubkeyHash(_) => BTCAddressType::P2PKH,
+            Target::ScriptHash(_) => BTCAddressType::P2SH,
+            Target::WitnessScriptHash(_) => BTCAddressType::P2WSH,
+            Target::OutputKey(_) => BTCAddressType::P2TR,
+            Target::PublicKey(..) => BTCAddressType::P2PK,
+        }
+    }
+
+    /// The addresses of the target for the types it is listed as. A public key hash listed without
+    /// type is shown as P2PKH, a public key listed as hash address type as the address of the key.
+    pub fn addresses(
+        &self,
+        address_types: AddressTypes,
+        network: Network,
+    ) -> Vec<(BTCAddressType, String)> {
+        match self {
+            Target::PubkeyHash(hash) => {
+                let mut addresses = Vec::new();
+                if address_types.contains(BTCAddressType::P2PKH)
+                    || !address_types.contains(BTCAddressType::P2WPKH)
+                {
+                    addresses.push((
+                        BTCAddressType::P2PKH,
+                        btc_address::p2pkh_address(hash, network),
+                    ));
+                }
+                if address_types.contains(BTCAddressType::P2WPKH) {
+                    addresses.push((
+                        BTCAddressType::P2WPKH,
+                        btc_address::p2wpkh_address(hash, network),
+                    ));
+                }
+                addresses
+            }
+            Target::ScriptHash(hash) => vec![(
+                BTCAddressType::P2SH,
+                btc_address::p2sh_address(hash, network),
+            )],
+            Target::WitnessScriptHash(hash) => vec![(
+                BTCAddressType::P2WSH,
+                btc_address::p2wsh_address(hash, network),
+            )],
+            Target::OutputKey(key) => vec![(
+                BTCAddressType::P2TR,
+                btc_address::p2tr_address(key, network),
+            )],
+            Target::PublicKey(key, compressed) => {
+                let serialized = serialize(key, *compressed);
+                let hash = hash_util::hash160(&serialized);
+                public_key_types(address_types)
+                    .into_iter()
+                    .map(|address_type| {
+                        let address = match address_type {
+                            BTCAddressType::P2PKH => btc_address::p2pkh_address(&hash, network),
+                            BTCAddressType::P2WPKH => btc_address::p2wpkh_address(&hash, network),
+                            BTCAddressType::P2SH => btc_address::p2sh_address(
+                                &hash_util::p2sh_p2wpkh_script_hash(&hash),
+                                network,
+                            ),
+                            _ => serialized.iter().map(|b| format!("{:02x}", b)).collect(),
+                        };
+                        (address_type, address)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The target as type tagged hex for the types it is listed as, ready for an address file. A
+    /// public key hash listed without type is tagged as P2PKH.
+    pub fn tagged(&self, address_types: AddressTypes) -> Vec<String> {
+        let tagged = |address_type, bytes: &[u8]| {
+            btc_address::type_tagged(address_type, bytes).expect("target types have a tag")
+        };
+        match self {
+            Target::PubkeyHash(hash) => {
+                let mut lines = Vec::new();
+                if address_types.contains(BTCAddressType::P2PKH)
+                    || !address_types.contains(BTCAddressType::P2WPKH)
+                {
+                    lines.push(tagged(BTCAddressType::P2PKH, hash.as_bytes()));
+                }
+                if address_types.contains(BTCAddressType::P2WPKH) {
+                    lines.push(tagged(BTCAddressType::P2WPKH, hash.as_bytes()));
+                }
+                lines
+            }
+            Target::ScriptHash(hash) => vec![tagged(BTCAddressType::P2SH, hash.as_bytes())],
+            Target::WitnessScriptHash(hash) => {
+                vec![tagged(BTCAddressType::P2WSH, hash.as_bytes())]
+            }
+            Target::OutputKey(key) => vec![tagged(BTCAddressType::P2TR, key.as_bytes())],
+            Target::PublicKey(key, compressed) => {
+                let serialized = serialize(key, *compressed);
+                public_key_types(address_types)
+                    .into_iter()
+                    .map(|address_type| tagged(address_type, &serialized))
+                    .collect()
+            }
+        }
+    }
+}
+
+fn serialize(key: &PublicKey, compressed: bool) -> Vec<u8> {
+    if compressed {
+        key.serialize().to_vec()
+    } else {
+        key.serialize_uncompressed().to_vec()
+    }
+}
+
+/// The types a public key target is listed as: P2PK and the hash addresses of the key it was
+/// revealed by, P2PK alone if it is listed as neither.
+fn public_key_types(address_types: AddressTypes) -> Vec<BTCAddressType> {
+    let hash_types = [
+        BTCAddressType::P2PKH,
+        BTCAddressType::P2WPKH,
+        BTCAddressType::P2SH,
+    ];
+    let mut types: Vec<BTCAddressType> = hash_types
+        .into_iter()
+        .filter(|address_type| address_types.contains(*address_type))
+        .collect();
+    if types.is_empty() || address_types.contains(BTCAddressType::P2PK) {
+        types.insert(0, BTCAddressType::P2PK);
+    }
+    types
+}
+
+/// A set of labels of the address files a target is listed in, as indexes into `Targets::labels`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Labels(u64);
+
+impl Labels {
+    /// Number of labels a target set can have
+    pub const MAX: usize = 64;
+
+    pub fn of(index: usize) -> Self {
+        Labels(1 << index)
+    }
+
+    pub fn union(self, other: Labels) -> Self {
+        Labels(self.0 | other.0)
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.0 & (1 << index) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// What is known about a target besides what the collider matches it by.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TargetInfo {
+    /// Balance in satoshis, if the address file has a balance column
+    pub balance: Option<u64>,
+    /// Types the target is listed as in the address file
+    pub address_types: AddressTypes,
+    /// Labels of the address files the target is listed in
+    pub labels: Labels,
+    /// Balance of each type, once a target is listed with balances as more than one type
+    pub type_balances: Option<Box<[(BTCAddressType, u64)]>>,
+}
+
+impl TargetInfo {
+    /// Combines the info of a target listed more than once. A repeated listing of the same type
+    /// keeps the larger balance of that type, the balances of different types, e.g. of the P2PKH
+    /// and P2WPKH addresses of a public key hash, add up. The result does not depend on the order.
+    pub fn merge(&mut self, other: TargetInfo) {
+        match (self.balance, other.balance) {
+            (Some(_), Some(_)) => {
+                let mut balances = self.balances_by_type();
+                for (address_type, balance) in other.balances_by_type() {
+                    match balances
+                        .iter_mut()
+                        .find(|(listed, _)| *listed == address_type)
+                    {
+                        Some((_, listed_balance)) => *listed_balance = balance.max(*listed_balance),
+                        None => balances.push((address_type, balance)),
+                    }
+                }
+                self.balance = Some(
+                    balances
+                        .iter()
+                        .fold(0u64, |total, (_, balance)| total.saturating_add(*balance)),
+                );
+                self.type_balances = (balances.len() > 1).then(|| balances.into_boxed_slice());
+            }
+            (None, Some(_)) => {
+                self.balance = other.balance;
+                self.type_balances = other.type_balances;
+            }
+            _ => {}
+        }
+        self.address_types = self.address_types.union(other.address_types);
+        self.labels = self.labels.union(other.labels);
+    }
+
+    /// The balance of the target as the address type, 0 for a type without balance of its own.
+    pub fn balance_of(&self, address_type: BTCAddressType) -> Option<u64> {
+        self.balance.map(|_| {
+            self.balances_by_type()
+                .into_iter()
+                .find(|(listed, _)| *listed == address_type)
+                .map_or(0, |(_, balance)| balance)
+        })
+    }
+
+    /// The balance of each type. Without balances by type the whole balance counts as the first
+    /// type, like the first line of a target written with its balance.
+    fn balances_by_type(&self) -> Vec<(BTCAddressType, u64)> {
+        match (
+            &self.type_balances,
+            self.balance,
+            self.address_types.first(),
+        ) {
+            (Some(balances), _, _) => balances.to_vec(),
+            (None, Some(balance), Some(address_type)) => vec![(address_type, balance)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Size of one of the hash sets of `Targets` or of its info table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoreStats {
+    pub name: &'static str,
+    pub len: usize,
+    pub capacity: usize,
+    /// Estimated heap memory in bytes: every slot of the capacity holds an entry and a control byte
+    pub bytes: usize,
+}
+
+impl StoreStats {
+    fn of<T>(name: &'static str, len: usize, capacity: usize) -> Self {
+        StoreStats {
+            name,
+            len,
+            capacity,
+            bytes: capacity * (size_of::<T>() + 1),
+        }
+    }
+
+    fn of_set<K>(name: &'static str, store: &HashSet<K>) -> Self {
+        Self::of::<K>(name, store.len(), store.capacity())
+    }
+}
+
+/// Estimated memory of all targets in one way of storing them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutEstimate {
+    pub name: &'static str,
+    pub bytes: usize,
+}
+
+/// Heap memory of a hash table holding `len` entries at the load factor of 7/8, with a control
+/// byte per bucket.
+fn hash_table_bytes(len: usize, entry_size: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (len * 8).div_ceil(7).next_power_of_two() * (entry_size + 1)
+}
+
+/// Everything the collider matches candidate keys against, grouped by what has to be derived from a
+/// candidate public key to find it. The sets only hold what is matched, the info of a target is
+/// looked up once it matched.
+#[derive(Debug, Default)]
+pub struct Targets {
+    /// HASH160 of a public key, used by P2PKH and P2WPKH outputs
+    pub pubkey_hashes: HashSet<H160>,
+    /// HASH160 of a redeem script, used by P2SH outputs
+    pub script_hashes: HashSet<H160>,
+    /// SHA256 of a witness script, used by P2WSH outputs
+    pub witness_script_hashes: HashSet<H256>,
+    /// Tweaked x-only output key, used by P2TR outputs
+    pub output_keys: HashSet<H256>,
+    /// Public key of P2PK outputs in compressed serialization
+    pub compressed_public_keys: HashSet<PublicKey>,
+    /// Public key of P2PK outputs in uncompressed serialization
+    pub uncompressed_public_keys: HashSet<PublicKey>,
+    /// Info of the targets whose info differs from the implied one, see `Targets::implied_info`
+    infos: HashMap<Target, TargetInfo>,
+    /// Labels of the loaded address files, `Labels` of a target index into it
+    pub labels: Vec<String>,
+}
+
+impl Targets {
+    pub fn new() -> Targets {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pubkey_hashes.len()
+            + self.script_hashes.len()
+            + self.witness_script_hashes.len()
+            + self.output_keys.len()
+            + self.compressed_public_keys.len()
+            + self.uncompressed_public_keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The label set of the given label, adding the label if it is new.
+    pub fn add_label(&mut self, label: &str) -> Result<Labels, Error> {
+        let index = match self.labels.iter().position(|l| l == label) {
+            Some(index) => index,
+            None if self.labels.len() < Labels::MAX => {
+                self.labels.push(label.to_string());
+                self.labels.len() - 1
+            }
+            None => return Err(Error::TooManyLabels(label.to_string())),
+        };
+        Ok(Labels::of(index))
+    }
+
+    pub fn label_names(&self, labels: Labels) -> Vec<String> {
+        self.labels
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| labels.contains(*index))
+            .map(|(_, label)| label.clone())
+            .collect()
+    }
+
+    /// Every target, in no particular order.
+    pub fn targets(&self) -> impl Iterator<Item = Target> + '_ {
+        let pubkey_hashes = self.pubkey_hashes.iter().map(|h| Target::PubkeyHash(*h));
+        let script_hashes = self.script_hashes.iter().map(|h| Target::ScriptHash(*h));
+        let witness_script_hashes = self
+            .witness_script_hashes
+            .iter()
+            .map(|h| Target::WitnessScriptHash(*h));
+        let output_keys = self.output_keys.iter().map(|k| Target::OutputKey(*k));
+        let compressed = self
+            .compressed_public_keys
+            .iter()
+            .map(|k| Target::PublicKey(*k, true));
+        let uncompressed = self
+            .uncompressed_public_keys
+            .iter()
+            .map(|k| Target::PublicKey(*k, false));
+        pubkey_hashes
+            .chain(script_hashes)
+            .chain(witness_script_hashes)
+            .chain(output_keys)
+            .chain(compressed)
+            .chain(uncompressed)
+    }
+
+    /// Every target with its info, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Target, TargetInfo)> + '_ {
+        self.targets().map(|target| {
+            let info = self.info(&target);
+            (target, info)
+        })
+    }
+
+    pub fn contains(&self, target: &Target) -> bool {
+        match target {
+            Target::PubkeyHash(hash) => self.pubkey_hashes.contains(hash),
+            Target::ScriptHash(hash) => self.script_hashes.contains(hash),
+            Target::WitnessScriptHash(hash) => self.witness_script_hashes.contains(hash),
+            Target::OutputKey(key) => self.output_keys.contains(key),
+            Target::PublicKey(key, true) => self.compressed_public_keys.contains(key),
+            Target::PublicKey(key, false) => self.uncompressed_public_keys.contains(key),
+        }
+    }
+
+    /// The info of the target if it is listed.
+    pub fn get(&self, target: &Target) -> Option<TargetInfo> {
+        self.contains(target).then(|| self.info(target))
+    }
+
+    /// Whether any target has a balance, i.e. whether the address file had a balance column.
+    pub fn has_balances(&self) -> bool {
+        // the implied info has no balance
+        self.infos.values().any(|info| info.balance.is_some())
+    }
+
+    /// Lists the target, merging its info into that of an earlier listing. True if it was listed
+    /// with the same address types before.
+    pub fn insert(&mut self, target: Target, info: TargetInfo) -> bool {
+        let implied = self.implied_info(&target);
+        let listed = match &target {
+            Target::PubkeyHash(hash) => !self.pubkey_hashes.insert(*hash),
+            Target::ScriptHash(hash) => !self.script_hashes.insert(*hash),
+            Target::WitnessScriptHash(hash) => !self.witness_script_hashes.insert(*hash),
+            Target::OutputKey(key) => !self.output_keys.insert(*key),
+            Target::PublicKey(key, true) => !self.compressed_public_keys.insert(*key),
+            Target::PublicKey(key, false) => !self.uncompressed_public_keys.insert(*key),
+        };
+        if !listed {
+            if info != implied {
+                self.infos.insert(target, info);
+            }
+            return false;
+        }
+
+        let mut merged = self.infos.get(&target).cloned().unwrap_or(implied.clone());
+        let duplicate = merged.address_types.union(info.address_types) == merged.address_types;
+        merged.merge(info);
+        if merged == implied {
+            self.infos.remove(&target);
+        } else {
+            self.infos.insert(target, merged);
+        }
+        duplicate
+    }
+
+    pub fn remove(&mut self, target: &Target) -> Option<TargetInfo> {
+        let removed = match target {
+            Target::PubkeyHash(hash) => self.pubkey_hashes.remove(hash),
+            Target::ScriptHash(hash) => self.script_hashes.remove(hash),
+            Target::WitnessScriptHash(hash) => self.witness_script_hashes.remove(hash),
+            Target::OutputKey(key) => self.output_keys.remove(key),
+            Target::PublicKey(key, true) => self.compressed_public_keys.remove(key),
+            Target::PublicKey(key, false) => self.uncompressed_public_keys.remove(key),
+        };
+        removed.then(|| {
+            self.infos
+                .remove(target)
+                .unwrap_or_else(|| self.implied_info(target))
+        })
+    }
+
+    fn info(&self, target: &Target) -> TargetInfo {
+        match self.infos.get(target) {
+            Some(info) => info.clone(),
+            None => self.implied_info(target),
+        }
+    }
+
+    /// The info of a target without entry in the info table: no balance, listed as its own type
+    /// and labelled with the first label. Most targets of a list without balance column have it.
+    fn implied_info(&self, target: &Target) -> TargetInfo {
+        TargetInfo {
+            balance: None,
+            address_types: AddressTypes::of(target.address_type()),
+            labels: if self.labels.is_empty() {
+                Labels::default()
+            } else {
+                Labels::of(0)
+            },
+            type_balances: None,
+        }
+    }
+
+    pub fn store_stats(&self) -> Vec<StoreStats> {
+        vec![
+            StoreStats::of_set("pubkey hashes", &self.pubkey_hashes),
+            StoreStats::of_set("script hashes", &self.script_hashes),
+            StoreStats::of_set("witness script hashes", &self.witness_script_hashes),
+            StoreStats::of_set("output keys", &self.output_keys),
+            StoreStats::of_set("compressed public keys", &self.compressed_public_keys),
+            StoreStats::of_set("uncompressed public keys", &self.uncompressed_public_keys),
+            StoreStats::of::<(Target, TargetInfo)>(
+                "target info",
+                self.infos.len(),
+                self.infos.capacity(),
+            ),
+        ]
+    }
+
+    /// Memory the targets would take in other layouts, estimated from the number of entries. Without
+    /// info table only matches are found, their balance, types and labels are lost.
+    pub fn layout_estimates(&self) -> Vec<LayoutEstimate> {
+        let stores = [
+            (self.pubkey_hashes.len(), size_of::<H160>()),
+            (self.script_hashes.len(), size_of::<H160>()),
+            (self.witness_script_hashes.len(), size_of::<H256>()),
+            (self.output_keys.len(), size_of::<H256>()),
+            (self.compressed_public_keys.len(), size_of::<PublicKey>()),
+            (self.uncompressed_public_keys.len(), size_of::<PublicKey>()),
+        ];
+        let info_size = size_of::<TargetInfo>();
+        let info_entry_size = size_of::<(Target, TargetInfo)>();
+        let sum = |bytes: &dyn Fn(usize, usize) -> usize| {
+            stores
+                .iter()
+                .map(|&(len, size)| bytes(len, size))
+                .sum::<usize>()
+        };
+
+        let hash_sets = sum(&hash_table_bytes);
+        let info_table = hash_table_bytes(self.infos.len(), info_entry_size);
+        let sorted_arrays = sum(&|len, size| len * size);
+        let sorted_info = self.infos.len() * info_entry_size;
+        vec![
+            LayoutEstimate {
+                name: "hash sets",
+                bytes: hash_sets,
+            },
+            LayoutEstimate {
+                name: "hash sets with info table",
+                bytes: hash_sets + info_table,
+            },
+            LayoutEstimate {
+                name: "hash maps with info per entry",
+                bytes: sum(&|len, size| hash_table_bytes(len, size + info_size)),
+            },
+            LayoutEstimate {
+                name: "sorted arrays",
+                bytes: sorted_arrays,
+            },
+            LayoutEstimate {
+                name: "sorted arrays with sorted info table",
+                bytes: sorted_arrays + sorted_info,
+            },
+        ]
+    }
+
+    /// Number of public key hashes listed both as P2PKH and as P2WPKH address.
+    pub fn pubkey_hash_overlap(&self) -> usize {
+        // the implied info of a public key hash lists it as P2PKH only
+        self.infos
+            .iter()
+            .filter(|(target, info)| {
+                matches!(target, Target::PubkeyHash(_))
+                    && info.address_types.contains(BTCAddressType::P2PKH)
+                    && info.address_types.contains(BTCAddressType::P2WPKH)
+            })
+            .count()
+    }
+}
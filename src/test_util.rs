@@ -0,0 +1,15 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Path of a temporary file of the tests, unique per name and test run.
+pub(crate) fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("btc-collider-rs-{}-{}", std::process::id(), name))
+}
+
+/// An empty temporary directory of the tests, unique per name and test run.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = temp_file(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,274 @@
+use crate::btc_address::BTCAddressType;
+use crate::error::Error;
+use crate::network::Network;
+use crate::script;
+use crate::serialize::{read_bytes, read_compact_size, read_u16, read_u64, read_varint, read_vec};
+use crate::targets::Target;
+use primitive_types::{H160, H256};
+use secp256k1::PublicKey;
+use std::io;
+use std::io::Read;
+
+/// Magic bytes at the start of the UTXO snapshots `dumptxoutset` writes since Bitcoin Core 28
+pub const SNAPSHOT_MAGIC: [u8; 5] = *b"utxo\xff";
+const SNAPSHOT_VERSION: u16 = 2;
+/// Number of script types the script compression of Bitcoin Core stores without the script
+const SPECIAL_SCRIPTS: u64 = 6;
+/// Bitcoin Core stores longer scripts as unspendable
+const MAX_SCRIPT_SIZE: u64 = 10_000;
+
+/// The header of a UTXO snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotMetadata {
+    /// Hash of the block the snapshot is taken at, in the byte order of the serialization
+    pub base_blockhash: H256,
+    pub coins_count: u64,
+}
+
+/// An unspent transaction output of a UTXO snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coin {
+    pub height: u32,
+    pub coinbase: bool,
+    /// Amount in satoshis
+    pub amount: u64,
+    pub address_type: BTCAddressType,
+    pub target: Option<Target>,
+}
+
+/// Reads the coins of a UTXO snapshot in Bitcoin Core's serialization: the metadata followed by
+/// the coins grouped by transaction, each with height, compressed amount and compressed script.
+pub struct SnapshotReader<R> {
+    reader: R,
+    file_name: String,
+    pub metadata: SnapshotMetadata,
+    coins_read: u64,
+    /// Coins of the current transaction that are not read yet
+    coins_left_in_tx: u64,
+}
+
+impl<R: Read> SnapshotReader<R> {
+    /// Reads the metadata, the snapshot has to be of the given network.
+    pub fn new(mut reader: R, file_name: &str, network: Network) -> Result<Self, Error> {
+        let io_error = |e| Error::io(file_name, e);
+        let magic: [u8; 5] = read_bytes(&mut reader).map_err(io_error)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(Error::invalid_data(file_name, "not a UTXO snapshot"));
+        }
+        let version = read_u16(&mut reader).map_err(io_error)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::invalid_data(
+                file_name,
+                format!("unsupported UTXO snapshot version {}", version),
+            ));
+        }
+        let message_start: [u8; 4] = read_bytes(&mut reader).map_err(io_error)?;
+        if !network.message_starts().contains(&message_start) {
+            return Err(Error::invalid_data(
+                file_name,
+                format!("UTXO snapshot is not of {}", network),
+            ));
+        }
+        let base_blockhash = H256(read_bytes(&mut reader).map_err(io_error)?);
+        let coins_count = read_u64(&mut reader).map_err(io_error)?;
+
+        Ok(SnapshotReader {
+            reader,
+            file_name: file_name.to_string(),
+            metadata: SnapshotMetadata {
+                base_blockhash,
+                coins_count,
+            },
+            coins_read: 0,
+            coins_left_in_tx: 0,
+        })
+    }
+
+    fn read_coin(&mut self) -> io::Result<Coin> {
+        while self.coins_left_in_tx == 0 {
+            let _txid: [u8; 32] = read_bytes(&mut self.reader)?;
+            self.coins_left_in_tx = read_compact_size(&mut self.reader)?;
+        }
+        self.coins_left_in_tx -= 1;
+
+        let _vout = read_compact_size(&mut self.reader)?;
+        let code = read_varint(&mut self.reader)?;
+        let amount = decompress_amount(read_varint(&mut self.reader)?);
+        let (address_type, target) = self.read_script()?;
+        Ok(Coin {
+            height: (code >> 1) as u32,
+            coinbase: code & 1 == 1,
+            amount,
+            address_type,
+            target,
+        })
+    }
+
+    /// Reads a script in the compression of Bitcoin Core: P2PKH, P2SH and P2PK scripts are stored
+    /// as hash or x coordinate behind their type, all other scripts behind their length plus the
+    /// number of special types.
+    fn read_script(&mut self) -> io::Result<(BTCAddressType, Option<Target>)> {
+        let size = read_varint(&mut self.reader)?;
+        Ok(match size {
+            0 => (
+                BTCAddressType::P2PKH,
+                Some(Target::PubkeyHash(H160(read_bytes(&mut self.reader)?))),
+            ),
+            1 => (
+                BTCAddressType::P2SH,
+                Some(Target::ScriptHash(H160(read_bytes(&mut self.reader)?))),
+            ),
+            2..=5 => {
+                let x: [u8; 32] = read_bytes(&mut self.reader)?;
+                // 4 and 5 are uncompressed keys with the parity of y in the lowest bit
+                let prefix = if size < 4 { size } else { size - 2 };
+                let mut compressed = [0u8; 33];
+                compressed[0] = prefix as u8;
+                compressed[1..].copy_from_slice(&x);
+                match PublicKey::from_slice(&compressed) {
+                    Ok(public_key) => (
+                        BTCAddressType::P2PK,
+                        Some(Target::PublicKey(public_key, size < 4)),
+                    ),
+                    Err(_) => (BTCAddressType::MISC, None),
+                }
+            }
+            _ => {
+                let len = size - SPECIAL_SCRIPTS;
+                if len > MAX_SCRIPT_SIZE {
+                    io::copy(&mut (&mut self.reader).take(len), &mut io::sink())?;
+                    return Ok((BTCAddressType::MISC, None));
+                }
+                let script = read_vec(&mut self.reader, len as usize)?;
+                script::output_target(&script)
+            }
+        })
+    }
+}
+
+impl<R: Read> Iterator for SnapshotReader<R> {
+    type Item = Result<Coin, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.coins_read == self.metadata.coins_count {
+            return None;
+        }
+        self.coins_read += 1;
+        let coin = self.read_coin().map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::invalid_data(
+                &self.file_name,
+                format!(
+                    "UTXO snapshot ends after {} of {} coins",
+                    self.coins_read - 1,
+                    self.metadata.coins_count
+                ),
+            ),
+            _ => Error::io(&self.file_name, e),
+        });
+        if coin.is_err() {
+            self.coins_read = self.metadata.coins_count;
+        }
+        Some(coin)
+    }
+}
+
+/// Reverses the amount compression of Bitcoin Core, which stores trailing decimal zeros as
+/// exponent.
+pub fn decompress_amount(x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    let mut x = x - 1;
+    let mut e = x % 10;
+    x /= 10;
+    let mut n = if e < 9 {
+        let d = x % 9 + 1;
+        x /= 9;
+        x * 10 + d
+    } else {
+        x + 1
+    };
+    while e > 0 {
+        n *= 10;
+        e -= 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::btc_address;
+    use crate::btc_address::BTCAddressType;
+    use crate::error::Error;
+    use crate::network::Network;
+    use crate::targets::Target;
+    use crate::utxo_snapshot::{decompress_amount, SnapshotReader};
+    use parameterized_macro::parameterized;
+
+    const SNAPSHOT: &str = "addresses/utxo_snapshot.dat";
+
+    #[parameterized(compressed = {0x0, 0x1, 0x7, 0x9, 0x32, 0x1406f40}, amount = {
+    0, 1, 1_000_000, 100_000_000, 5_000_000_000, 2_100_000_000_000_000
+    })]
+    fn can_decompress_amount(compressed: u64, amount: u64) {
+        assert_eq!(amount, decompress_amount(compressed));
+    }
+
+    #[test]
+    fn can_read_snapshot() {
+        let file = std::fs::File::open(SNAPSHOT).unwrap();
+        let snapshot = SnapshotReader::new(file, SNAPSHOT, Network::Regtest).unwrap();
+        assert_eq!(8, snapshot.metadata.coins_count);
+
+        let coins: Vec<_> = snapshot.map(|coin| coin.unwrap()).collect();
+        let types: Vec<_> = coins.iter().map(|coin| coin.address_type).collect();
+        assert_eq!(
+            vec![
+                BTCAddressType::P2PKH,
+                BTCAddressType::P2WPKH,
+                BTCAddressType::P2SH,
+                BTCAddressType::P2PK,
+                BTCAddressType::P2PK,
+                BTCAddressType::P2TR,
+                BTCAddressType::P2WSH,
+                BTCAddressType::MISC,
+            ],
+            types
+        );
+        assert_eq!((100, true), (coins[0].height, coins[0].coinbase));
+        assert_eq!(5_000_000_000, coins[0].amount);
+
+        let hash =
+            btc_address::hex_to_160_bit_hash("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(Some(Target::PubkeyHash(hash)), coins[0].target);
+        assert_eq!(Some(Target::PubkeyHash(hash)), coins[1].target);
+
+        let (key_2, _) = btc_address::p2pk_output_to_public_key(
+            "04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5\
+            1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a",
+        )
+        .unwrap();
+        assert_eq!(Some(Target::PublicKey(key_2, false)), coins[4].target);
+        assert_eq!(4000, coins[4].amount);
+    }
+
+    #[test]
+    fn fails_on_other_network() {
+        let file = std::fs::File::open(SNAPSHOT).unwrap();
+        let result = SnapshotReader::new(file, SNAPSHOT, Network::Mainnet);
+        assert!(matches!(result, Err(Error::InvalidData { .. })));
+    }
+
+    #[test]
+    fn fails_on_truncated_snapshot() {
+        let bytes = std::fs::read(SNAPSHOT).unwrap();
+        let truncated = &bytes[..bytes.len() - 10];
+        let snapshot = SnapshotReader::new(truncated, SNAPSHOT, Network::Regtest).unwrap();
+        let results: Vec<_> = snapshot.collect();
+        assert_eq!(8, results.len());
+        assert!(matches!(
+            results.last(),
+            Some(Err(Error::InvalidData { .. }))
+        ));
+    }
+}
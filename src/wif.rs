@@ -1,3 +1,4 @@
+use crate::network::Network;
 use num_bigint::BigUint;
 use sha2::{Digest, Sha256};
 
@@ -9,7 +10,7 @@ pub fn wif_to_private_key(wif: &str) -> [u8; 32] {
     k
 }
 
-pub fn private_key_to_wif(private_key: &BigUint, compressed: bool) -> String {
+pub fn private_key_to_wif(private_key: &BigUint, compressed: bool, network: Network) -> String {
     // Step 1: Convert private key to 32-byte array
     let mut key_bytes = private_key.to_bytes_be();
 
@@ -18,8 +19,8 @@ pub fn private_key_to_wif(private_key: &BigUint, compressed: bool) -> String {
         key_bytes.insert(0, 0);
     }
 
-    // Step 2: Add version byte (0x80 for mainnet)
-    let mut extended_key = vec![0x80];
+    // Step 2: Add version byte (0x80 for mainnet, 0xef for the test networks)
+    let mut extended_key = vec![network.wif_version()];
     extended_key.extend_from_slice(&key_bytes);
 
     // Step 3: Add compression flag if compressed
@@ -72,7 +73,7 @@ mod tests {
     compressed = {false, true, false, false, false, true, false})]
     fn can_convert_private_key_to_wif(key: &str, expected_wif: &str, compressed: bool) {
         let private_key = BigUint::from_str_radix(key, 16).unwrap();
-        let wif = private_key_to_wif(&private_key, compressed);
+        let wif = private_key_to_wif(&private_key, compressed, Network::Mainnet);
         assert_eq!(expected_wif, wif);
 
         let private_key = wif_to_private_key(&wif);
@@ -81,4 +82,17 @@ mod tests {
             BigInt::from_bytes_be(Sign::Plus, &private_key).to_str_radix(16)
         );
     }
+
+    #[parameterized(network = {Network::Testnet, Network::Regtest}, compressed = {true, false}, expected_wif = {
+    "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA",
+    "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjJoQFacbgwmaKkrx",
+    })]
+    fn can_convert_private_key_to_test_network_wif(
+        network: Network,
+        compressed: bool,
+        expected_wif: &str,
+    ) {
+        let wif = private_key_to_wif(&BigUint::from(1u32), compressed, network);
+        assert_eq!(expected_wif, wif);
+    }
 }